@@ -7,6 +7,7 @@ pkgs.mkShell {
         xorg.libXi
         libGL
         libxkbcommon
+        alsa-lib
     ];
 
     LD_LIBRARY_PATH = builtins.concatStringsSep ":" [
@@ -14,5 +15,6 @@ pkgs.mkShell {
         "${pkgs.xorg.libXi}/lib"
         "${pkgs.libGL}/lib"
         "${pkgs.libxkbcommon}/lib"
+        "${pkgs.alsa-lib}/lib"
     ];
 }
@@ -1,7 +1,60 @@
-use macroquad::{prelude::*, rand};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use core::time::Duration;
+
+use crate::{Chip8Error, Quirks};
+
+mod assembler;
+mod builder;
+mod busy_wait;
+mod debug;
+mod describe;
+mod diff;
+mod disasm;
+mod features;
+#[cfg(all(test, feature = "std"))]
+mod fuzz;
+mod lint;
+mod lockstep;
+mod patch;
+mod quirk_report;
+mod rpl;
+mod savestate;
+mod taint;
+#[cfg(feature = "std")]
+mod trace;
+
+pub use assembler::assemble;
+pub use builder::Chip8Builder;
+pub use describe::describe_opcode;
+pub use diff::{framebuffer_diff, framebuffer_diff_overlay, StateDiff};
+pub use disasm::{disassemble, disassemble_labeled};
+#[cfg(feature = "std")]
+pub use features::static_opcode_histogram;
+pub use features::{required_features, supported_opcodes, unsupported_spec_opcodes, FeatureSet};
+pub use lint::{lint_jumps, LintWarning};
+pub use lockstep::lockstep_diverge;
+pub use quirk_report::QuirkReport;
+
+// The core has to build without std, so diagnostics are only printed when
+// std is around to print them.
+macro_rules! warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "std")]
+        eprintln!($($arg)*);
+    }};
+}
 
-#[derive(Debug)]
-enum Instruction {
+/// A decoded instruction, operands are register numbers (0x0 - 0xF),
+/// immediate values or addresses as encoded in the opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,                            // 0000
     Clear,                          // 00E0
     Return,                         // 00EE
     Jump(usize),                    // 1NNN
@@ -22,8 +75,8 @@ enum Instruction {
     SetXtoYshiftLeftOnce(u8, u8),   // 8XYE
     SkipOnXneqY(u8, u8),            // 9XY0
     LoadIndexRegister(u16),         // ANNN
-    JumpByRegister(usize),          // BNNN
-    LoadRegisterWithRandom(u8, u8), // CNNN
+    JumpByRegister(u8, usize),      // BNNN, or BXNN (SCHIP)
+    LoadRegisterWithRandom(u8, u8), // CXNN
     DrawSprite(u8, u8, u8),         // DXYN
     SkipIfPressed(u8),              // EX9E
     SkipNotPressed(u8),             // EXA1
@@ -36,6 +89,495 @@ enum Instruction {
     StoreRegisterInBCD(u8),         // FX33
     StoreRegistersInMemmory(u8),    // FX55
     FillRegisters(u8),              // FX65
+    LoadAudioPattern,               // F002 (XO-CHIP)
+    SetPitchFromRegister(u8),       // FX3A (XO-CHIP)
+    SelectPlanes(u8),               // FN01 (XO-CHIP)
+    StoreFlags(u8),                 // FX75 (SCHIP)
+    LoadFlags(u8),                  // FX85 (SCHIP)
+    LowResolution,                  // 00FE (SCHIP)
+    HighResolution,                 // 00FF (SCHIP)
+    ScrollDown(u8),                 // 00CN (SCHIP)
+    ScrollUp(u8),                   // 00DN (XO-CHIP)
+    ScrollRight,                    // 00FB (SCHIP)
+    ScrollLeft,                     // 00FC (SCHIP)
+}
+
+/// Unknown opcodes `recent_unknown_opcodes` remembers.
+const RECENT_UNKNOWN_LEN: usize = 16;
+
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// The hex digits 0 to F, 4x5 pixels each in the top nibble of 5 bytes,
+/// loaded at 0x50.
+#[rustfmt::skip]
+pub(crate) const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// Whether column `x` of a sprite row is lit, Chip8 uses big-endian so the
+/// leftmost pixel is the top bit.
+fn sprite_bit(row: u8, x: usize) -> bool {
+    row & (0x80 >> (x % 8)) != 0
+}
+
+/// Takes one 60hz tick off a timer, which stops at 0.
+fn count_down(timer: &mut u8) {
+    *timer = timer.saturating_sub(1);
+}
+
+/// Feeds `bytes` into the 64 bit FNV-1a `hash`.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    let mut hash = hash;
+    for byte in bytes {
+        hash = (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Turns an opcode into the instruction it encodes, `None` for unknown opcodes.
+fn decode(opcode: u16) -> Option<Instruction> {
+    match opcode & 0xF000 {
+        0x0000 => {
+            // ROMs are often padded with zeros, which isn't worth a warning
+            if opcode == 0x0000 {
+                Some(Instruction::Nop)
+            } else if opcode & 0x0FFF == 0x00E0 {
+                Some(Instruction::Clear)
+            } else if opcode & 0x0FFF == 0x00EE {
+                Some(Instruction::Return)
+            } else if opcode & 0x0FFF == 0x00FE {
+                Some(Instruction::LowResolution)
+            } else if opcode & 0x0FFF == 0x00FF {
+                Some(Instruction::HighResolution)
+            } else if opcode & 0x0FF0 == 0x00C0 {
+                Some(Instruction::ScrollDown((opcode & 0x000F) as u8))
+            } else if opcode & 0x0FF0 == 0x00D0 {
+                Some(Instruction::ScrollUp((opcode & 0x000F) as u8))
+            } else if opcode & 0x0FFF == 0x00FB {
+                Some(Instruction::ScrollRight)
+            } else if opcode & 0x0FFF == 0x00FC {
+                Some(Instruction::ScrollLeft)
+            } else {
+                None
+            }
+        }
+
+        0x1000 => {
+            let address = (opcode & 0x0FFF) as usize;
+            Some(Instruction::Jump(address))
+        }
+
+        0x2000 => {
+            let address = (opcode & 0x0FFF) as usize;
+            Some(Instruction::SubRoutine(address))
+        }
+
+        0x3000 => {
+            let register: u8 = (opcode >> 8 & 0x000F) as u8;
+            let value: u8 = (opcode & 0x00FF) as u8;
+            Some(Instruction::SkipOnXeqV(register, value))
+        }
+
+        0x4000 => {
+            let register: u8 = (opcode >> 8 & 0x000F) as u8;
+            let value: u8 = (opcode & 0x00FF) as u8;
+            Some(Instruction::SkipOnXneqV(register, value))
+        }
+
+        0x5000 => {
+            let x_register: u8 = (opcode >> 8 & 0x000F) as u8;
+            let y_register: u8 = (opcode >> 4 & 0x000F) as u8;
+            let value: u8 = (opcode & 0x000F) as u8;
+
+            if value == 0 {
+                Some(Instruction::SkipOnXeqY(x_register, y_register))
+            } else {
+                None
+            }
+        }
+
+        0x6000 => {
+            let register: u8 = (opcode >> 8 & 0x000F) as u8;
+            let value: u8 = (opcode & 0x00FF) as u8;
+            Some(Instruction::LoadNormalRegister(register, value))
+        }
+
+        0x7000 => {
+            let register: u8 = (opcode >> 8 & 0x000F) as u8;
+            let value: u8 = (opcode & 0x00FF) as u8;
+            Some(Instruction::AddToNormalRegister(register, value))
+        }
+
+        0x8000 => {
+            let x_register: u8 = (opcode >> 8 & 0x000F) as u8;
+            let y_register: u8 = (opcode >> 4 & 0x000F) as u8;
+            let op: u8 = (opcode & 0x000F) as u8;
+
+            match op {
+                0x0 => Some(Instruction::SetXtoY(x_register, y_register)),
+                0x1 => Some(Instruction::SetXtoXorY(x_register, y_register)),
+                0x2 => Some(Instruction::SetXtoXandY(x_register, y_register)),
+                0x3 => Some(Instruction::SetXtoXxorY(x_register, y_register)),
+                0x4 => Some(Instruction::AddYtoX(x_register, y_register)),
+                0x5 => Some(Instruction::SubYfromX(x_register, y_register)),
+                0x6 => Some(Instruction::SetXtoYshiftRightOnce(x_register, y_register)),
+                0x7 => Some(Instruction::SetXtoYMinusX(x_register, y_register)),
+                0xE => Some(Instruction::SetXtoYshiftLeftOnce(x_register, y_register)),
+                _ => None,
+            }
+        }
+
+        0x9000 => {
+            let x_register: u8 = (opcode >> 8 & 0x000F) as u8;
+            let y_register: u8 = (opcode >> 4 & 0x000F) as u8;
+            let value: u8 = (opcode & 0x000F) as u8;
+
+            if value == 0 {
+                Some(Instruction::SkipOnXneqY(x_register, y_register))
+            } else {
+                None
+            }
+        }
+
+        0xA000 => {
+            let value = opcode & 0x0FFF;
+            Some(Instruction::LoadIndexRegister(value))
+        }
+
+        // X is the register for the BXNN reading, see Quirks::jump_with_vx
+        0xB000 => {
+            let register = ((opcode & 0x0F00) >> 8) as u8;
+            let address = opcode & 0x0FFF;
+            Some(Instruction::JumpByRegister(register, address as usize))
+        }
+
+        0xC000 => {
+            let register: u8 = (opcode >> 8 & 0x000F) as u8;
+            let value: u8 = (opcode & 0x00FF) as u8;
+            Some(Instruction::LoadRegisterWithRandom(register, value))
+        }
+
+        0xD000 => {
+            let x_register: u8 = (opcode >> 8 & 0x000F) as u8;
+            let y_register: u8 = (opcode >> 4 & 0x000F) as u8;
+            let num_bytes: u8 = (opcode & 0x000F) as u8;
+
+            Some(Instruction::DrawSprite(x_register, y_register, num_bytes))
+        }
+
+        0xE000 => {
+            let register: u8 = (opcode >> 8 & 0x000F) as u8;
+
+            let sub_opcode = opcode & 0x00FF;
+            match sub_opcode {
+                0xA1 => Some(Instruction::SkipNotPressed(register)),
+                0x9E => Some(Instruction::SkipIfPressed(register)),
+                _ => None,
+            }
+        }
+
+        0xF000 => {
+            let value: u8 = (opcode >> 8 & 0x000F) as u8;
+
+            let sub_opcode = opcode & 0x00FF;
+            match sub_opcode {
+                0x07 => Some(Instruction::StoreDeelayInRegister(value)),
+                0x0A => Some(Instruction::WaitUserInput(value)),
+                0x15 => Some(Instruction::SetDeelayFromRegister(value)),
+                0x18 => Some(Instruction::SetSoundTimerFromRegister(value)),
+                0x1E => Some(Instruction::AddRegisterToIndex(value)),
+                0x29 => Some(Instruction::LoadFont(value)),
+                0x33 => Some(Instruction::StoreRegisterInBCD(value)),
+                0x55 => Some(Instruction::StoreRegistersInMemmory(value)),
+                0x65 => Some(Instruction::FillRegisters(value)),
+                0x02 if value == 0 => Some(Instruction::LoadAudioPattern),
+                0x3A => Some(Instruction::SetPitchFromRegister(value)),
+                0x01 => Some(Instruction::SelectPlanes(value)),
+                0x75 => Some(Instruction::StoreFlags(value)),
+                0x85 => Some(Instruction::LoadFlags(value)),
+                _ => None,
+            }
+        }
+
+        _ => None,
+    }
+}
+
+/// Turns an instruction back into its opcode, the inverse of `decode`.
+fn encode(instruction: Instruction) -> u16 {
+    let x = |register: u8| u16::from(register & 0xF) << 8;
+    let xy = |x_register: u8, y_register: u8| x(x_register) | u16::from(y_register & 0xF) << 4;
+    match instruction {
+        Instruction::Nop => 0x0000,
+        Instruction::Clear => 0x00E0,
+        Instruction::Return => 0x00EE,
+        Instruction::Jump(address) => 0x1000 | (address & 0x0FFF) as u16,
+        Instruction::SubRoutine(address) => 0x2000 | (address & 0x0FFF) as u16,
+        Instruction::SkipOnXeqV(register, value) => 0x3000 | x(register) | u16::from(value),
+        Instruction::SkipOnXneqV(register, value) => 0x4000 | x(register) | u16::from(value),
+        Instruction::SkipOnXeqY(x_register, y_register) => 0x5000 | xy(x_register, y_register),
+        Instruction::LoadNormalRegister(register, value) => 0x6000 | x(register) | u16::from(value),
+        Instruction::AddToNormalRegister(register, value) => {
+            0x7000 | x(register) | u16::from(value)
+        }
+        Instruction::SetXtoY(x_register, y_register) => 0x8000 | xy(x_register, y_register),
+        Instruction::SetXtoXorY(x_register, y_register) => 0x8001 | xy(x_register, y_register),
+        Instruction::SetXtoXandY(x_register, y_register) => 0x8002 | xy(x_register, y_register),
+        Instruction::SetXtoXxorY(x_register, y_register) => 0x8003 | xy(x_register, y_register),
+        Instruction::AddYtoX(x_register, y_register) => 0x8004 | xy(x_register, y_register),
+        Instruction::SubYfromX(x_register, y_register) => 0x8005 | xy(x_register, y_register),
+        Instruction::SetXtoYshiftRightOnce(x_register, y_register) => {
+            0x8006 | xy(x_register, y_register)
+        }
+        Instruction::SetXtoYMinusX(x_register, y_register) => 0x8007 | xy(x_register, y_register),
+        Instruction::SetXtoYshiftLeftOnce(x_register, y_register) => {
+            0x800E | xy(x_register, y_register)
+        }
+        Instruction::SkipOnXneqY(x_register, y_register) => 0x9000 | xy(x_register, y_register),
+        Instruction::LoadIndexRegister(address) => 0xA000 | (address & 0x0FFF),
+        Instruction::JumpByRegister(_, address) => 0xB000 | (address & 0x0FFF) as u16,
+        Instruction::LoadRegisterWithRandom(register, value) => {
+            0xC000 | x(register) | u16::from(value)
+        }
+        Instruction::DrawSprite(x_register, y_register, rows) => {
+            0xD000 | xy(x_register, y_register) | u16::from(rows & 0xF)
+        }
+        Instruction::SkipIfPressed(register) => 0xE09E | x(register),
+        Instruction::SkipNotPressed(register) => 0xE0A1 | x(register),
+        Instruction::StoreDeelayInRegister(register) => 0xF007 | x(register),
+        Instruction::WaitUserInput(register) => 0xF00A | x(register),
+        Instruction::SetDeelayFromRegister(register) => 0xF015 | x(register),
+        Instruction::SetSoundTimerFromRegister(register) => 0xF018 | x(register),
+        Instruction::AddRegisterToIndex(register) => 0xF01E | x(register),
+        Instruction::LoadFont(register) => 0xF029 | x(register),
+        Instruction::StoreRegisterInBCD(register) => 0xF033 | x(register),
+        Instruction::StoreRegistersInMemmory(register) => 0xF055 | x(register),
+        Instruction::FillRegisters(register) => 0xF065 | x(register),
+        Instruction::LoadAudioPattern => 0xF002,
+        Instruction::SetPitchFromRegister(register) => 0xF03A | x(register),
+        Instruction::SelectPlanes(planes) => 0xF001 | x(planes),
+        Instruction::StoreFlags(register) => 0xF075 | x(register),
+        Instruction::LoadFlags(register) => 0xF085 | x(register),
+        Instruction::LowResolution => 0x00FE,
+        Instruction::HighResolution => 0x00FF,
+        Instruction::ScrollDown(rows) => 0x00C0 | u16::from(rows & 0xF),
+        Instruction::ScrollUp(rows) => 0x00D0 | u16::from(rows & 0xF),
+        Instruction::ScrollRight => 0x00FB,
+        Instruction::ScrollLeft => 0x00FC,
+    }
+}
+
+impl Instruction {
+    /// Clears, draws, scrolls or switches resolution.
+    fn uses_display(self) -> bool {
+        use Instruction::*;
+        matches!(
+            self,
+            Clear
+                | DrawSprite(..)
+                | LowResolution
+                | HighResolution
+                | ScrollDown(_)
+                | ScrollUp(_)
+                | ScrollRight
+                | ScrollLeft
+        )
+    }
+
+    /// The opcode pattern the instruction was decoded from, e.g. `"6XNN"`.
+    pub fn pattern(self) -> &'static str {
+        match self {
+            Instruction::Nop => "0000",
+            Instruction::Clear => "00E0",
+            Instruction::Return => "00EE",
+            Instruction::Jump(_) => "1NNN",
+            Instruction::SubRoutine(_) => "2NNN",
+            Instruction::SkipOnXeqV(..) => "3XNN",
+            Instruction::SkipOnXneqV(..) => "4XNN",
+            Instruction::SkipOnXeqY(..) => "5XY0",
+            Instruction::LoadNormalRegister(..) => "6XNN",
+            Instruction::AddToNormalRegister(..) => "7XNN",
+            Instruction::SetXtoY(..) => "8XY0",
+            Instruction::SetXtoXorY(..) => "8XY1",
+            Instruction::SetXtoXandY(..) => "8XY2",
+            Instruction::SetXtoXxorY(..) => "8XY3",
+            Instruction::AddYtoX(..) => "8XY4",
+            Instruction::SubYfromX(..) => "8XY5",
+            Instruction::SetXtoYshiftRightOnce(..) => "8XY6",
+            Instruction::SetXtoYMinusX(..) => "8XY7",
+            Instruction::SetXtoYshiftLeftOnce(..) => "8XYE",
+            Instruction::SkipOnXneqY(..) => "9XY0",
+            Instruction::LoadIndexRegister(_) => "ANNN",
+            Instruction::JumpByRegister(..) => "BNNN",
+            Instruction::LoadRegisterWithRandom(..) => "CXNN",
+            Instruction::DrawSprite(..) => "DXYN",
+            Instruction::SkipIfPressed(_) => "EX9E",
+            Instruction::SkipNotPressed(_) => "EXA1",
+            Instruction::StoreDeelayInRegister(_) => "FX07",
+            Instruction::WaitUserInput(_) => "FX0A",
+            Instruction::SetDeelayFromRegister(_) => "FX15",
+            Instruction::SetSoundTimerFromRegister(_) => "FX18",
+            Instruction::AddRegisterToIndex(_) => "FX1E",
+            Instruction::LoadFont(_) => "FX29",
+            Instruction::StoreRegisterInBCD(_) => "FX33",
+            Instruction::StoreRegistersInMemmory(_) => "FX55",
+            Instruction::FillRegisters(_) => "FX65",
+            Instruction::LoadAudioPattern => "F002",
+            Instruction::SetPitchFromRegister(_) => "FX3A",
+            Instruction::SelectPlanes(_) => "FN01",
+            Instruction::StoreFlags(_) => "FX75",
+            Instruction::LoadFlags(_) => "FX85",
+            Instruction::LowResolution => "00FE",
+            Instruction::HighResolution => "00FF",
+            Instruction::ScrollDown(_) => "00CN",
+            Instruction::ScrollUp(_) => "00DN",
+            Instruction::ScrollRight => "00FB",
+            Instruction::ScrollLeft => "00FC",
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Prints the instruction in the usual CHIP-8 assembly syntax, e.g. `LD V1, 0x2A`.
+    ///
+    /// BNNN always prints as `JP V0, NNN`, even though it adds VX instead with
+    /// the `jump_with_vx` quirk.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Nop => write!(f, "NOP"),
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::Jump(address) => write!(f, "JP 0x{address:03X}"),
+            Instruction::SubRoutine(address) => write!(f, "CALL 0x{address:03X}"),
+            Instruction::SkipOnXeqV(x, value) => write!(f, "SE V{x:X}, 0x{value:02X}"),
+            Instruction::SkipOnXneqV(x, value) => write!(f, "SNE V{x:X}, 0x{value:02X}"),
+            Instruction::SkipOnXeqY(x, y) => write!(f, "SE V{x:X}, V{y:X}"),
+            Instruction::LoadNormalRegister(x, value) => write!(f, "LD V{x:X}, 0x{value:02X}"),
+            Instruction::AddToNormalRegister(x, value) => write!(f, "ADD V{x:X}, 0x{value:02X}"),
+            Instruction::SetXtoY(x, y) => write!(f, "LD V{x:X}, V{y:X}"),
+            Instruction::SetXtoXorY(x, y) => write!(f, "OR V{x:X}, V{y:X}"),
+            Instruction::SetXtoXandY(x, y) => write!(f, "AND V{x:X}, V{y:X}"),
+            Instruction::SetXtoXxorY(x, y) => write!(f, "XOR V{x:X}, V{y:X}"),
+            Instruction::AddYtoX(x, y) => write!(f, "ADD V{x:X}, V{y:X}"),
+            Instruction::SubYfromX(x, y) => write!(f, "SUB V{x:X}, V{y:X}"),
+            Instruction::SetXtoYshiftRightOnce(x, y) => write!(f, "SHR V{x:X}, V{y:X}"),
+            Instruction::SetXtoYMinusX(x, y) => write!(f, "SUBN V{x:X}, V{y:X}"),
+            Instruction::SetXtoYshiftLeftOnce(x, y) => write!(f, "SHL V{x:X}, V{y:X}"),
+            Instruction::SkipOnXneqY(x, y) => write!(f, "SNE V{x:X}, V{y:X}"),
+            Instruction::LoadIndexRegister(address) => write!(f, "LD I, 0x{address:03X}"),
+            Instruction::JumpByRegister(_, address) => write!(f, "JP V0, 0x{address:03X}"),
+            Instruction::LoadRegisterWithRandom(x, value) => {
+                write!(f, "RND V{x:X}, 0x{value:02X}")
+            }
+            Instruction::DrawSprite(x, y, rows) => write!(f, "DRW V{x:X}, V{y:X}, {rows}"),
+            Instruction::SkipIfPressed(x) => write!(f, "SKP V{x:X}"),
+            Instruction::SkipNotPressed(x) => write!(f, "SKNP V{x:X}"),
+            Instruction::StoreDeelayInRegister(x) => write!(f, "LD V{x:X}, DT"),
+            Instruction::WaitUserInput(x) => write!(f, "LD V{x:X}, K"),
+            Instruction::SetDeelayFromRegister(x) => write!(f, "LD DT, V{x:X}"),
+            Instruction::SetSoundTimerFromRegister(x) => write!(f, "LD ST, V{x:X}"),
+            Instruction::AddRegisterToIndex(x) => write!(f, "ADD I, V{x:X}"),
+            Instruction::LoadFont(x) => write!(f, "LD F, V{x:X}"),
+            Instruction::StoreRegisterInBCD(x) => write!(f, "LD B, V{x:X}"),
+            Instruction::StoreRegistersInMemmory(x) => write!(f, "LD [I], V{x:X}"),
+            Instruction::FillRegisters(x) => write!(f, "LD V{x:X}, [I]"),
+            Instruction::LoadAudioPattern => write!(f, "AUDIO"),
+            Instruction::SetPitchFromRegister(x) => write!(f, "PITCH V{x:X}"),
+            Instruction::SelectPlanes(planes) => write!(f, "PLANE {planes}"),
+            Instruction::StoreFlags(x) => write!(f, "LD R, V{x:X}"),
+            Instruction::LoadFlags(x) => write!(f, "LD V{x:X}, R"),
+            Instruction::LowResolution => write!(f, "LOW"),
+            Instruction::HighResolution => write!(f, "HIGH"),
+            Instruction::ScrollDown(rows) => write!(f, "SCD {rows}"),
+            Instruction::ScrollUp(rows) => write!(f, "SCU {rows}"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+        }
+    }
+}
+
+/// What a single `Chip8::step` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepOutcome {
+    /// Address the opcode was fetched from.
+    pub pc: usize,
+    pub opcode: u16,
+    /// `None` if the opcode is unknown or was handled by an opcode override.
+    pub instruction: Option<Instruction>,
+    /// PC didn't just move on to the next instruction, because of a jump, a
+    /// call, a return or a skip that was taken.
+    pub branched: bool,
+    /// A sprite was drawn and turned off at least one pixel.
+    pub collision: bool,
+    /// The machine is stuck on FX0A until a key is released.
+    pub blocked: bool,
+}
+
+/// Things that happened while running, for frontends and debugging tools.
+/// Collect them with `Chip8::drain_events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Event {
+    /// A sprite ran over the edge of the screen and was wrapped around or
+    /// clipped, following `Quirks::clip_sprites`. Only reported after
+    /// `set_report_edge_draws(true)`.
+    EdgeDraw { wrapped: bool },
+    /// `cycles` instructions ran without anything being drawn or cleared,
+    /// often a sign the ROM isn't CHIP-8 or is loaded at the wrong address.
+    /// Only reported after `set_no_display_activity_threshold`, and once
+    /// until the display is used again.
+    NoDisplayActivity { cycles: u64 },
+    /// An arithmetic or draw instruction read a register that wasn't written
+    /// since the last reset. Only reported after
+    /// `set_track_uninit_registers(true)`.
+    UninitRegisterRead(u8),
+    /// The ROM entered a small loop from `start` to the jump back at `end`
+    /// that only polls the timers or the keypad. Only reported after
+    /// `set_report_busy_waits(true)`, once until the loop is left.
+    BusyWait { start: usize, end: usize },
+}
+
+/// When the keypad state given to `set_key` becomes visible to the ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeySampleGranularity {
+    /// Once per 60hz frame, what most games expect
+    #[default]
+    PerFrame,
+    /// Before every instruction, for the lowest latency
+    PerInstruction,
+}
+
+pub type OpcodeHandler = Box<dyn FnMut(&mut Chip8, u16) + Send>;
+
+/// Replaces the default behaviour of every opcode where `opcode & mask == pattern`.
+struct OpcodeOverride {
+    mask: u16,
+    pattern: u16,
+    handler: OpcodeHandler,
+}
+
+impl fmt::Debug for OpcodeOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpcodeOverride")
+            .field("mask", &self.mask)
+            .field("pattern", &self.pattern)
+            .finish_non_exhaustive()
+    }
 }
 
 #[derive(Debug)]
@@ -52,629 +594,1509 @@ pub struct Chip8 {
     // count down with a frequency of 60hz
     deelay: u8,
 
-    // cound down only for values greater than 0x01
+    // count down with a frequency of 60hz
     sound_timer: u8,
 
+    // XO-CHIP audio: 128 1-bit samples played while the sound timer is
+    // active, at a sample rate of 4000 * 2 ^ ((pitch - 64) / 48) Hz
+    audio_pattern: [u8; 16],
+    audio_pitch: u8,
+
+    // only silences is_beeping, the sound timer keeps counting down
+    muted: bool,
+
+    // frames left to beep for, at least min_beep_frames after a nonzero
+    // write to the sound timer so single frame blips can be heard
+    beep_frames: u8,
+    min_beep_frames: u8,
+
     // 0 = black, 1 = white
+    // with XO-CHIP bit planes a pixel is the mask of the planes it's lit
+    // on, bit 0 for the first and bit 1 for the second
     // to draw a sprite we XOR with the screen data
     // if the sprite is offscreen we modulo 64 and 32
     // every sprite is 8 pixels wide and height [1, 15]
-    screen: [[u8; 64]; 32],
+    // or 16x16 for SCHIP DXY0
+    // in low-res only the top-left 64x32 pixels are used
+    screen: [[u8; 128]; 64],
+    hires: bool,
+
+    // pixels the latest DXYN turned off
+    last_collision_count: u32,
+
+    // planes FN01 selected for drawing, clearing and scrolling, only the
+    // first one unless a ROM asks for more
+    planes: u8,
 
     screen_update: bool,
 
-    stack: Vec<usize>,
+    // the original interpreter only had room for 16 nested calls
+    stack: [usize; 16],
+    sp: usize,
+
+    // state of the 16 keys of the keypad, fed by the frontend through set_key
+    key_input: [bool; 16],
+
+    // the keypad as the ROM sees it, latched from key_input
+    keys: [bool; 16],
+    key_sample_granularity: KeySampleGranularity,
+    latch_keys: bool,
+
+    // FX0A waits for a key to be pressed and released
+    waiting_for_key: bool,
+    released_key: Option<u8>,
+
+    // frames every key has been held for, and after how many frames and
+    // then every how many a held key also satisfies FX0A
+    held_frames: [u32; 16],
+    key_repeat: Option<(u32, u32)>,
+
+    // xorshift state used by CXNN
+    rng: u32,
+    // values CXNN takes before going back to the generator, and the
+    // values it got when logging is on
+    rng_replay: VecDeque<u8>,
+    rng_log: Option<Vec<u8>>,
+
+    // patches written to RAM and the bytes each one replaced
+    patches: Vec<(usize, Vec<u8>)>,
+    patched_over: Vec<Vec<u8>>,
+
+    // a paused machine neither executes instructions nor counts down timers
+    paused: bool,
+
+    // instructions executed for every 60hz frame by run_frame
+    cycles_per_frame: usize,
+
+    // executed instructions and frames since the last reset
+    cycles: u64,
+    frames: u64,
+
+    // wall time given to advance that didn't add up to a whole frame yet, in
+    // 1/60 ns so a frame is exactly one second worth and nothing gets rounded
+    pending_time: u128,
+
+    // opcodes that didn't decode to any instruction and were skipped
+    unknown_opcodes: u64,
+    // where the latest different ones were, the oldest first
+    recent_unknown: Vec<(usize, u16)>,
+
+    // why the machine stopped, an instruction the ROM ran couldn't be done
+    fault: Option<Chip8Error>,
+
+    // what step fetched last, set before it runs so hooks and panic
+    // handlers can tell what was going on
+    last_opcode: u16,
+    last_instruction: Option<Instruction>,
+
+    // FNV-1a over the (pc, opcode) pairs executed since the last reset
+    signature: u64,
+
+    overrides: Vec<OpcodeOverride>,
+
+    quirks: Quirks,
+
+    // stack depth at the end of each frame, the newest stack_history_len ones
+    stack_history: Vec<usize>,
+    stack_history_len: usize,
+
+    // key changes from load_input_script as (cycle, key, pressed), sorted by
+    // cycle, and the next one to apply
+    input_script: Vec<(u64, u8, bool)>,
+    input_script_pos: usize,
+
+    // not yet collected by drain_events
+    events: Vec<Chip8Event>,
+    report_edge_draws: bool,
+    // cycle count of the last display instruction, for NoDisplayActivity
+    last_display_cycle: u64,
+    no_display_threshold: u64,
+    no_display_reported: bool,
+
+    // registers written since the reset, a bit per register
+    written_registers: u16,
+    track_uninit_registers: bool,
+
+    // the polling loop being run, if it was reported
+    report_busy_waits: bool,
+    busy_wait: Option<(usize, usize)>,
+
+    // SCHIP RPL user flags, FX75 and FX85 save and restore registers here
+    // and they outlive resets, like on the HP-48 they came from
+    rpl_flags: [u8; 16],
+    #[cfg(feature = "std")]
+    rpl_file: Option<std::path::PathBuf>,
+
+    // start_trace_file writes a line per executed instruction here
+    #[cfg(feature = "std")]
+    trace_file: Option<std::io::BufWriter<std::fs::File>>,
+    // lines left before trace_first_cycles stops tracing on its own
+    #[cfg(feature = "std")]
+    trace_remaining: Option<u64>,
+
+    // kept around so the machine can be reset
+    layout: MemoryLayout,
+    rom: Vec<u8>,
+}
+
+/// What gets put in RAM besides the ROM, so a reset rebuilds the same memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MemoryLayout {
+    builtin_font: bool,
+    // where the ROM goes and PC starts
+    load_address: usize,
+    // what goes in 0x000..0x200 before the font, on the VIP the interpreter
+    interpreter: Option<[u8; 0x200]>,
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        MemoryLayout {
+            builtin_font: true,
+            load_address: 0x200,
+            interpreter: None,
+        }
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::blank()
+    }
 }
 
 impl Chip8 {
+    #[cfg(feature = "std")]
     pub fn new(path: String) -> Self {
-        // TODO: load the font
         let rom_data = std::fs::read(path).expect("No source file found");
+        Self::from_bytes(&rom_data)
+    }
+
+    /// Like `new`, with the compatibility profile the ROM was written for.
+    #[cfg(feature = "std")]
+    pub fn with_quirks(path: String, quirks: Quirks) -> Self {
+        let mut machine = Self::new(path);
+        machine.set_quirks(quirks);
+        machine
+    }
+
+    /// A machine with the font loaded but no program, PC still starts at 0x200.
+    pub fn blank() -> Self {
+        Self::from_bytes(&[])
+    }
+
+    /// Builds a machine with `rom` loaded at 0x200, without touching the file system.
+    ///
+    /// Panics if the ROM doesn't fit in memory, see `try_from_bytes`.
+    pub fn from_bytes(rom: &[u8]) -> Self {
+        Self::try_from_bytes(rom).expect("ROM doesn't fit in memory")
+    }
+
+    /// Builds a machine running `opcodes`, stored big-endian from 0x200 on.
+    pub fn from_opcodes(opcodes: &[u16]) -> Self {
+        let rom: Vec<u8> = opcodes.iter().flat_map(|op| op.to_be_bytes()).collect();
+        Self::from_bytes(&rom)
+    }
+
+    /// Like `from_bytes`, but returns `RomTooLarge` for ROMs that don't fit in memory.
+    pub fn try_from_bytes(rom: &[u8]) -> Result<Self, Chip8Error> {
+        Self::with_layout(rom, MemoryLayout::default())
+    }
+
+    /// Starts configuring a machine, for the options `from_bytes` doesn't offer.
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::default()
+    }
+
+    fn with_layout(rom: &[u8], layout: MemoryLayout) -> Result<Self, Chip8Error> {
         let mut ram = [0; 4 * 1024];
-        for i in 0..rom_data.len() {
-            ram[0x200 + i] = rom_data[i];
-        }
-
-        let font = [
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80  // F
-        ];
-        for i in 0..font.len() {
-            ram[0x50 + i] = font[i];
+        let start = layout.load_address;
+        let max = ram.len().saturating_sub(start);
+        if rom.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+
+        if let Some(interpreter) = layout.interpreter {
+            ram[..interpreter.len()].copy_from_slice(&interpreter);
+        }
+        if layout.builtin_font {
+            ram[0x50..0x50 + FONT.len()].copy_from_slice(&FONT);
         }
+        ram[start..start + rom.len()].copy_from_slice(rom);
 
-        Chip8 {
+        Ok(Chip8 {
             v: [0; 16],
             i: 0,
-            pc: 0x200,
+            pc: start,
             ram,
             deelay: 0,
             sound_timer: 0,
-            screen: [[0; 64]; 32],
+            audio_pattern: [0; 16],
+            audio_pitch: 64,
+            muted: false,
+            beep_frames: 0,
+            min_beep_frames: 1,
+            screen: [[0; 128]; 64],
+            hires: false,
+            last_collision_count: 0,
+            planes: 1,
             screen_update: false,
-            stack: Vec::new(),
-        }
+            stack: [0; 16],
+            sp: 0,
+            key_input: [false; 16],
+            keys: [false; 16],
+            key_sample_granularity: KeySampleGranularity::PerFrame,
+            latch_keys: true,
+            waiting_for_key: false,
+            released_key: None,
+            held_frames: [0; 16],
+            key_repeat: None,
+            rng: 0x2F6B_1A3D,
+            rng_replay: VecDeque::new(),
+            rng_log: None,
+            patches: Vec::new(),
+            patched_over: Vec::new(),
+            paused: false,
+            cycles_per_frame: 10,
+            cycles: 0,
+            frames: 0,
+            pending_time: 0,
+            unknown_opcodes: 0,
+            recent_unknown: Vec::new(),
+            fault: None,
+            last_opcode: 0,
+            last_instruction: None,
+            signature: FNV_OFFSET,
+            overrides: Vec::new(),
+            quirks: Quirks::default(),
+            stack_history: Vec::new(),
+            stack_history_len: 0,
+            input_script: Vec::new(),
+            input_script_pos: 0,
+            events: Vec::new(),
+            report_edge_draws: false,
+            last_display_cycle: 0,
+            no_display_threshold: 0,
+            no_display_reported: false,
+            written_registers: 0,
+            track_uninit_registers: false,
+            report_busy_waits: false,
+            busy_wait: None,
+            rpl_flags: [0; 16],
+            #[cfg(feature = "std")]
+            rpl_file: None,
+            #[cfg(feature = "std")]
+            trace_file: None,
+            #[cfg(feature = "std")]
+            trace_remaining: None,
+            layout,
+            rom: rom.to_vec(),
+        })
     }
 
-    /// It will convert the input keys from the original keypad values
-    /// to a modern keyboard. Since we are using macroquad we are going
-    /// to return the coresponsing enum value from macroquad KeyCode.
+    /// Puts the machine back in the state it had right after loading the ROM,
+    /// a cold reset.
     ///
-    /// First seen it: https://multigesture.net/articles/how-to-write-an-emulator-chip-8-interpreter/
-    /// and thought it is a good idea.
-    fn keypad_to_keyboard(old_key: u8) -> KeyCode {
-        match old_key {
-            0x1 => KeyCode::Key1,
-            0x2 => KeyCode::Key2,
-            0x3 => KeyCode::Key3,
-            0xC => KeyCode::Key4,
-            0x4 => KeyCode::Q,
-            0x5 => KeyCode::W,
-            0x6 => KeyCode::E,
-            0xD => KeyCode::R,
-            0x7 => KeyCode::A,
-            0x8 => KeyCode::S,
-            0x9 => KeyCode::D,
-            0xE => KeyCode::F,
-            0xA => KeyCode::Z,
-            0x0 => KeyCode::X,
-            0xB => KeyCode::C,
-            0xF => KeyCode::V,
-            _ => KeyCode::Unknown,
-        }
-    }
-
-    fn keyboard_to_keypad(key: KeyCode) -> u8 {
-        match key {
-            KeyCode::Key1 => 0x1,
-            KeyCode::Key2 => 0x2,
-            KeyCode::Key3 => 0x3,
-            KeyCode::Key4 => 0xC,
-            KeyCode::Q => 0x4,
-            KeyCode::W => 0x5,
-            KeyCode::E => 0x6,
-            KeyCode::R => 0xD,
-            KeyCode::A => 0x7,
-            KeyCode::S => 0x8,
-            KeyCode::D => 0x9,
-            KeyCode::F => 0xE,
-            KeyCode::Z => 0xA,
-            KeyCode::X => 0x0,
-            KeyCode::C => 0xB,
-            KeyCode::V => 0xF,
-            _ => 254,
-        }
+    /// The keypad, the pause and the mute flags belong to the frontend and are
+    /// kept, as is the configuration.
+    pub fn reset(&mut self) {
+        let machine = Self::with_layout(&self.rom, self.layout);
+        let mut machine = machine.expect("ROM doesn't fit in memory");
+        machine.keep_settings(self);
+        *self = machine;
     }
 
-    pub fn tick(&mut self) {
-        let decay_speed = 1;
-        if self.deelay > 0 {
-            self.deelay = if self.deelay < decay_speed { 0 } else { self.deelay - decay_speed };
-        }
+    /// Like `reset`, but RAM is left as it is, code the ROM rewrote and
+    /// patches included, like the reset button on some machines. Only the
+    /// registers, PC, timers, stack and screen start over.
+    pub fn warm_reset(&mut self) {
+        let ram = self.ram;
+        let patches = core::mem::take(&mut self.patches);
+        let patched_over = core::mem::take(&mut self.patched_over);
+        self.reset();
+        self.ram = ram;
+        self.patches = patches;
+        self.patched_over = patched_over;
+    }
 
-        if self.sound_timer > 1 {
-            self.sound_timer = if self.sound_timer < decay_speed { 0 } else { self.sound_timer - decay_speed };
+    /// Moves everything that isn't machine state over from `old`.
+    fn keep_settings(&mut self, old: &mut Chip8) {
+        self.key_input = old.key_input;
+        self.held_frames = old.held_frames;
+        self.key_repeat = old.key_repeat;
+        self.key_sample_granularity = old.key_sample_granularity;
+        self.paused = old.paused;
+        self.muted = old.muted;
+        self.min_beep_frames = old.min_beep_frames;
+        self.cycles_per_frame = old.cycles_per_frame;
+        self.overrides = core::mem::take(&mut old.overrides);
+        self.quirks = old.quirks;
+        self.report_edge_draws = old.report_edge_draws;
+        self.no_display_threshold = old.no_display_threshold;
+        self.track_uninit_registers = old.track_uninit_registers;
+        self.report_busy_waits = old.report_busy_waits;
+        self.stack_history_len = old.stack_history_len;
+        self.input_script = core::mem::take(&mut old.input_script);
+        self.rng_replay = core::mem::take(&mut old.rng_replay);
+        self.rng_log = old.rng_log.as_ref().map(|_| Vec::new());
+        self.rpl_flags = old.rpl_flags;
+        #[cfg(feature = "std")]
+        {
+            self.rpl_file = old.rpl_file.take();
+            self.trace_file = old.trace_file.take();
+            self.trace_remaining = old.trace_remaining;
         }
+        self.layout = old.layout;
+        self.rom = core::mem::take(&mut old.rom);
     }
 
-    pub fn update_screen(&self) {
-        let pixel_width = screen_width() / 64.0;
-        let pixel_height = screen_height() / 32.0;
+    /// Replaces the ROM and resets the machine.
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        self.rom = rom.to_vec();
+        self.reset();
+    }
 
-        for y in 0..self.screen.len() {
-            for x in 0..self.screen[0].len() {
-                if self.screen[y][x] == 1 {
-                    draw_rectangle(
-                        pixel_width * x as f32,
-                        pixel_height * y as f32,
-                        pixel_width,
-                        pixel_height,
-                        WHITE,
-                    )
-                } else {
-                    draw_rectangle(
-                        pixel_width * x as f32,
-                        pixel_height * y as f32,
-                        pixel_width,
-                        pixel_height,
-                        BLACK,
-                    )
-                }
-            }
-        }
+    pub fn pause(&mut self) {
+        self.paused = true;
     }
 
-    fn exec(&mut self, instruction: Instruction) {
-        match instruction {
-            Instruction::Clear => {
-                for i in 0..self.screen.len() {
-                    for j in 0..self.screen[i].len() {
-                        self.screen[i][j] = 0;
-                    }
-                }
-            }
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
 
-            Instruction::LoadNormalRegister(register, value) => {
-                self.v[register as usize] = value;
-            }
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
 
-            Instruction::LoadIndexRegister(value) => {
-                self.i = value;
-            }
+    /// Updates the state of a keypad key (0x0 - 0xF).
+    ///
+    /// EX9E and EXA1 only see the change once the keypad gets sampled again,
+    /// see `set_key_sample_granularity`. FX0A reacts to the release right away.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        let key = key & 0xF;
+        if self.waiting_for_key && self.key_input[key as usize] && !pressed {
+            self.released_key = Some(key);
+        }
+        self.key_input[key as usize] = pressed;
+    }
 
-            Instruction::SkipOnXeqV(register, value) => {
-                self.pc += 2 * (self.v[register as usize] == value) as usize;
-            }
+    /// Lets a key held for `delay_frames` frames count for FX0A as if it was
+    /// released, and then again every `rate_frames` frames, so holding a key
+    /// scrolls through a menu. A `rate_frames` of 0 repeats only once and a
+    /// `delay_frames` of 0 (the default) turns repeating off.
+    pub fn set_key_repeat(&mut self, delay_frames: u32, rate_frames: u32) {
+        self.key_repeat = (delay_frames > 0).then_some((delay_frames, rate_frames));
+    }
 
-            Instruction::SkipOnXneqV(register, value) => {
-                self.pc += 2 * (self.v[register as usize] != value) as usize;
+    /// Schedules key presses and releases, e.g. to play back a demo.
+    ///
+    /// The script is a whitespace separated list of `key:down@cycle` and
+    /// `key:up@cycle` entries, with the key in hex. Each change is applied
+    /// through `set_key` right before the instruction that runs once `cycle`
+    /// instructions have been executed. It replaces any earlier script and
+    /// plays again from the start after a reset.
+    ///
+    /// ```text
+    /// 5:down@100 5:up@110
+    /// ```
+    pub fn load_input_script(&mut self, script: &str) -> Result<(), Chip8Error> {
+        let mut changes = Vec::new();
+        for (entry, word) in script.split_whitespace().enumerate() {
+            let invalid = Chip8Error::InvalidInputScript { entry };
+            let (key, rest) = word.split_once(':').ok_or(invalid.clone())?;
+            let (action, cycle) = rest.split_once('@').ok_or(invalid.clone())?;
+
+            let key = u8::from_str_radix(key, 16).ok().filter(|key| *key <= 0xF);
+            let pressed = match action {
+                "down" => true,
+                "up" => false,
+                _ => return Err(invalid),
+            };
+            match (key, cycle.parse()) {
+                (Some(key), Ok(cycle)) => changes.push((cycle, key, pressed)),
+                _ => return Err(invalid),
             }
+        }
 
-            Instruction::SkipOnXeqY(x_register, y_register) => {
-                self.pc += 2 * (self.v[x_register as usize] == self.v[y_register as usize]) as usize;
-            }
+        // stable, so changes for the same cycle keep their order
+        changes.sort_by_key(|(cycle, _, _)| *cycle);
+        self.input_script = changes;
+        self.input_script_pos = self
+            .input_script
+            .partition_point(|(cycle, _, _)| *cycle < self.cycles);
+        Ok(())
+    }
 
-            Instruction::SkipOnXneqY(x_register, y_register) => {
-                self.pc += 2 * (self.v[x_register as usize] != self.v[y_register as usize]) as usize;
+    fn apply_input_script(&mut self) {
+        while let Some(&(cycle, key, pressed)) = self.input_script.get(self.input_script_pos) {
+            if cycle > self.cycles {
+                break;
             }
+            self.set_key(key, pressed);
+            self.input_script_pos += 1;
+        }
+    }
 
-            Instruction::SetXtoY(x_register, y_register) => {
-                self.v[x_register as usize] = self.v[y_register as usize];
-            }
+    /// Whether the ROM is blocked on FX0A until a key is released. Frames
+    /// still run and return as usual meanwhile, so the frontend keeps drawing.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key
+    }
 
-            Instruction::SetXtoXorY(x_register, y_register) => {
-                self.v[x_register as usize] |= self.v[y_register as usize];
-            }
+    /// Whether the ROM currently sees `key` as pressed.
+    pub fn is_key_down(&self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
 
-            Instruction::SetXtoXandY(x_register, y_register) => {
-                self.v[x_register as usize] &= self.v[y_register as usize];
-            }
+    /// Instructions `run_frame` executes before counting the timers down.
+    pub fn cycles_per_frame(&self) -> usize {
+        self.cycles_per_frame
+    }
 
-            Instruction::SetXtoXxorY(x_register, y_register) => {
-                self.v[x_register as usize] ^= self.v[y_register as usize];
-            }
+    /// Runs `instructions_per_second` from now on, rounded to a whole number
+    /// every 60hz frame like `Chip8Builder::clock_hz`, and fails the same way
+    /// on a clock too slow for that.
+    pub fn set_clock_speed(&mut self, instructions_per_second: u32) -> Result<(), Chip8Error> {
+        self.cycles_per_frame = builder::cycles_for_clock(instructions_per_second)?;
+        Ok(())
+    }
 
-            Instruction::AddYtoX(x_register, y_register) => {
-                let mut value: u16 = self.v[x_register as usize] as u16;
-                value += self.v[y_register as usize] as u16;
+    pub fn set_key_sample_granularity(&mut self, granularity: KeySampleGranularity) {
+        self.key_sample_granularity = granularity;
+    }
 
-                self.v[x_register as usize] = value as u8;
+    /// 0 = black, 1 = white, only the top-left `width()` x `height()` pixels are in use.
+    /// XO-CHIP ROMs drawing on the second bit plane also make pixels 2 (lit on
+    /// the second plane only) and 3 (lit on both).
+    pub fn framebuffer(&self) -> &[[u8; 128]; 64] {
+        &self.screen
+    }
 
-                // Set VF to 01 if a carry occurs, else 00
-                self.v[0xF] = 0;
-                if value > 0xFF {
-                    self.v[0xF] = 1;
-                }
-            }
+    /// Whether the pixel at `(x, y)` is lit on any plane, false outside the display.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        x < self.width() && y < self.height() && self.screen[y][x] != 0
+    }
 
-            Instruction::SubYfromX(x_register, y_register) => {
-                let (value, overflowed) =
-                    self.v[x_register as usize].overflowing_sub(self.v[y_register as usize]);
+    /// How many pixels the most recent sprite draw turned off, VF only says
+    /// whether there were any.
+    pub fn last_collision_count(&self) -> u32 {
+        self.last_collision_count
+    }
 
-                self.v[x_register as usize] = value;
+    /// Row `y` of the display as bits, like `framebuffer_packed` the leftmost
+    /// pixel is the most significant: pixel `x` is bit `width() - 1 - x`. A
+    /// low-res row only uses the lower 64 bits. Rows outside the display are 0.
+    pub fn row_bits(&self, y: usize) -> u128 {
+        if y >= self.height() {
+            return 0;
+        }
+        let mut bits = 0;
+        for pixel in &self.screen[y][..self.width()] {
+            bits = bits << 1 | u128::from(*pixel != 0);
+        }
+        bits
+    }
 
-                // Set VF to 00 if a borrow occurs, else 01
-                self.v[0xF] = 1;
-                if overflowed {
-                    self.v[0xF] = 0;
-                }
-            }
+    /// Width of the display in the current resolution, 64 or 128 for SCHIP high-res.
+    pub fn width(&self) -> usize {
+        if self.hires {
+            128
+        } else {
+            64
+        }
+    }
 
-            Instruction::SetXtoYshiftRightOnce(x_register, y_register) => {
-                self.v[x_register as usize] = self.v[y_register as usize];
-                self.v[0xF] = self.v[x_register as usize] & (1 << 0);
-                self.v[x_register as usize] >>= 1;
-            }
+    /// Height of the display in the current resolution, 32 or 64 for SCHIP high-res.
+    pub fn height(&self) -> usize {
+        if self.hires {
+            64
+        } else {
+            32
+        }
+    }
 
-            Instruction::SetXtoYshiftLeftOnce(x_register, y_register) => {
-                self.v[x_register as usize] = self.v[y_register as usize];
-                self.v[0xF] = self.v[x_register as usize] >> 7 & 1;
-                self.v[x_register as usize] <<= 1;
-            }
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
 
-            Instruction::SetXtoYMinusX(x_register, y_register) => {
-                let (value, overflowed) =
-                    self.v[y_register as usize].overflowing_sub(self.v[x_register as usize]);
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
 
-                self.v[x_register as usize] = value;
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
 
-                // Set VF to 00 if a borrow occurs, else 01
-                self.v[0xF] = 1;
-                if overflowed {
-                    self.v[0xF] = 0;
-                }
-            }
+    /// Events that happened since the last call, oldest first.
+    pub fn drain_events(&mut self) -> Vec<Chip8Event> {
+        core::mem::take(&mut self.events)
+    }
 
-            Instruction::AddToNormalRegister(register, value) => {
-                let value: u8 = (self.v[register as usize] as u16 + value as u16) as u8;
-                self.v[register as usize] = value;
-            }
+    /// Reports a `Chip8Event::EdgeDraw` for every sprite with a lit pixel
+    /// past the edge of the screen. Meant for debugging, the events pile up
+    /// until drained.
+    pub fn set_report_edge_draws(&mut self, report: bool) {
+        self.report_edge_draws = report;
+    }
 
-            Instruction::JumpByRegister(address) => {
-                self.pc = address + self.v[0] as usize;
-            }
+    /// Reports a `Chip8Event::NoDisplayActivity` once `cycles` instructions
+    /// ran without clearing, drawing, scrolling or switching resolution.
+    /// 0 (the default) turns the check off.
+    pub fn set_no_display_activity_threshold(&mut self, cycles: u64) {
+        self.no_display_threshold = cycles;
+    }
 
-            Instruction::LoadRegisterWithRandom(register, value) => {
-                let random_value: u8 = rand::rand() as u8;
-                self.v[register as usize] = value & random_value;
-            }
+    /// Runs `handler` instead of the default behaviour for every opcode where
+    /// `opcode & mask == pattern`, e.g. to try out nonstandard instructions.
+    ///
+    /// Overrides are checked in the order they were added, the first match wins.
+    pub fn set_opcode_override(&mut self, mask: u16, pattern: u16, handler: OpcodeHandler) {
+        self.overrides.push(OpcodeOverride {
+            mask,
+            pattern,
+            handler,
+        });
+    }
 
-            Instruction::DrawSprite(x_register, y_register, num_bytes) => {
-                let x_start = self.v[x_register as usize] % 64;
-                let y_start = self.v[y_register as usize] % 32;
-                self.v[0xF] = 0;
+    /// Whether the frontend should be playing a tone right now.
+    pub fn is_beeping(&self) -> bool {
+        !self.muted && (self.sound_timer > 0 || self.beep_frames > 0)
+    }
 
-                assert!(num_bytes <= 0xF);
-                for y in 0..num_bytes {
-                    let sprite_data = self.ram[self.i as usize + y as usize];
-                    for x in 0..8 {
-                        if y + y_start >= 32 || x + x_start >= 64 {
-                            continue;
-                        }
+    /// Keeps `is_beeping` true for at least `frames` frames after the ROM sets
+    /// the sound timer to anything but 0, for audio backends that can't play
+    /// a sound as short as one frame. The timer the ROM reads back is left
+    /// alone. 1, the default, plays what the ROM asked for.
+    pub fn set_min_beep_frames(&mut self, frames: u8) {
+        self.min_beep_frames = frames;
+    }
 
-                        // Chip8 uses big-endian
-                        let mut bit_value = 0;
-                        if sprite_data & (1 << (7 - x)) != 0 {
-                            bit_value = 1;
-                        }
+    /// Mutes the beep without touching the sound timer, which ROMs can read.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
 
-                        let prev_pixel_value =
-                            self.screen[(y + y_start) as usize][(x + x_start) as usize];
-                        self.screen[(y + y_start) as usize][(x + x_start) as usize] ^= bit_value;
+    /// XO-CHIP audio pattern, 128 samples of 1 bit, MSB first.
+    pub fn audio_pattern(&self) -> &[u8; 16] {
+        &self.audio_pattern
+    }
 
-                        if prev_pixel_value == 1
-                            && self.screen[(y + y_start) as usize][(x + x_start) as usize] == 0
-                        {
-                            self.v[0xF] = 1;
-                        }
-                    }
-                }
+    /// XO-CHIP pitch register, 64 (the default) plays the pattern at 4000Hz.
+    pub fn audio_pitch(&self) -> u8 {
+        self.audio_pitch
+    }
 
-                self.screen_update = true;
-                self.update_screen();
-            }
+    /// XO-CHIP planes 00E0, DXYN and the scrolls work on, a bit per plane
+    /// like FN01 takes them. Only the first plane (1) at the start.
+    pub fn selected_planes(&self) -> u8 {
+        self.planes
+    }
 
-            Instruction::WaitUserInput(register) => {
-                let keys_pressed = get_keys_pressed();
-                if keys_pressed.len() != 1 {
-                    self.pc -= 2;
-                } else {
-                    for key in keys_pressed {
-                        self.v[register as usize] = Self::keyboard_to_keypad(key);
-                    }
+    /// Selects planes like FN01 does, failing on a mask other than 0 to 3.
+    pub fn set_selected_planes(&mut self, mask: u8) -> Result<(), Chip8Error> {
+        if mask > 0b11 {
+            return Err(Chip8Error::InvalidPlaneMask { mask });
+        }
+        self.planes = mask;
+        Ok(())
+    }
+
+    /// Range of RAM covered by `len` bytes starting at `address`, if it fits.
+    fn ram_range(&self, address: usize, len: usize) -> Result<Range<usize>, Chip8Error> {
+        match address.checked_add(len) {
+            Some(end) if end <= self.ram.len() => Ok(address..end),
+            _ => Err(Chip8Error::MemoryOutOfBounds { address, len }),
+        }
+    }
+
+    /// Writes sprite data at `addr`, mostly useful to set up draw tests.
+    pub fn set_sprite(&mut self, addr: usize, rows: &[u8]) -> Result<(), Chip8Error> {
+        let range = self.ram_range(addr, rows.len())?;
+        self.ram[range].copy_from_slice(rows);
+        Ok(())
+    }
+
+    /// Points the index register at `addr`, e.g. at a sprite written with `set_sprite`.
+    pub fn set_index(&mut self, addr: u16) {
+        self.i = addr;
+    }
+
+    /// Records every random byte CXNN gets from now on, before it's masked
+    /// with NN, see `rng_log`. Turning it off drops what was recorded.
+    pub fn set_rng_logging(&mut self, log: bool) {
+        self.rng_log = log.then(Vec::new);
+    }
+
+    /// The random bytes recorded since logging was turned on or the machine
+    /// reset, pass them to `replay_rng` to get the same run again.
+    pub fn rng_log(&self) -> &[u8] {
+        self.rng_log.as_deref().unwrap_or(&[])
+    }
+
+    /// Makes the next CXNNs use `values` in order, before going back to the
+    /// generator once they run out. Kept on `reset`.
+    pub fn replay_rng(&mut self, values: Vec<u8>) {
+        self.rng_replay = values.into();
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        let byte = match self.rng_replay.pop_front() {
+            Some(byte) => byte,
+            None => {
+                // xorshift32
+                self.rng ^= self.rng << 13;
+                self.rng ^= self.rng >> 17;
+                self.rng ^= self.rng << 5;
+                (self.rng >> 24) as u8
+            }
+        };
+        if let Some(log) = &mut self.rng_log {
+            log.push(byte);
+        }
+        byte
+    }
+
+    /// Counts the delay and sound timers down, once per 60hz frame. The timers
+    /// are frozen while paused, so FX07 keeps reading the same value.
+    pub fn tick(&mut self) {
+        if self.paused {
+            return;
+        }
+
+        // a new frame starts, its first instruction samples the keypad
+        self.latch_keys = true;
+        self.repeat_held_keys();
+
+        count_down(&mut self.deelay);
+        count_down(&mut self.sound_timer);
+        count_down(&mut self.beep_frames);
+    }
+
+    fn repeat_held_keys(&mut self) {
+        for (held, pressed) in self.held_frames.iter_mut().zip(self.key_input) {
+            *held = if pressed { held.saturating_add(1) } else { 0 };
+        }
+
+        let Some((delay, rate)) = self.key_repeat else {
+            return;
+        };
+        if !self.waiting_for_key || self.released_key.is_some() {
+            return;
+        }
+        let repeats = |held: u32| match held.checked_sub(delay) {
+            Some(0) => true,
+            Some(since) => rate > 0 && since.is_multiple_of(rate),
+            None => false,
+        };
+        if let Some(key) = self.held_frames.iter().position(|held| repeats(*held)) {
+            self.released_key = Some(key as u8);
+        }
+    }
+
+    /// Returns the framebuffer as a packed bitfield of 64 * 32 / 8 = 256 bytes,
+    /// or 128 * 64 / 8 = 1024 bytes in SCHIP high-res.
+    ///
+    /// Pixels are stored row-major, 8 per byte, with the leftmost pixel in the
+    /// most significant bit, a pixel is set if it's lit on any plane. This
+    /// layout is meant as a stable wire format for external tools and does
+    /// not depend on how `screen` is stored internally.
+    pub fn framebuffer_packed(&self) -> Vec<u8> {
+        self.pack_screen(0b11)
+    }
+
+    /// Packs the pixels lit on any of `planes` like `framebuffer_packed`.
+    fn pack_screen(&self, planes: u8) -> Vec<u8> {
+        let width = self.width();
+        let mut packed = vec![0; width * self.height() / 8];
+        for (y, row) in self.screen[..self.height()].iter().enumerate() {
+            for (x, pixel) in row[..width].iter().enumerate() {
+                if *pixel & planes != 0 {
+                    packed[(y * width + x) / 8] |= 0x80 >> (x % 8);
                 }
             }
+        }
+        packed
+    }
+
+    /// FNV-1a hash of `framebuffer_packed`, to compare screens against known
+    /// good ones without storing them.
+    pub fn framebuffer_hash(&self) -> u64 {
+        fnv1a(FNV_OFFSET, &self.framebuffer_packed())
+    }
 
-            Instruction::LoadFont(value) => {
-                self.i = 0x50 + 5 * value as u16;
+    /// Renders the display as text, one line per row, `#` for lit pixels and
+    /// `.` for dark ones.
+    pub fn screen_to_string(&self) -> String {
+        let mut text = String::with_capacity((self.width() + 1) * self.height());
+        for row in &self.screen[..self.height()] {
+            for pixel in &row[..self.width()] {
+                text.push(if *pixel != 0 { '#' } else { '.' });
             }
+            text.push('\n');
+        }
+        text
+    }
 
-            Instruction::SkipIfPressed(register) => {
-                let key_to_check = Self::keypad_to_keyboard(self.v[register as usize]);
-                if is_key_down(key_to_check) {
-                    self.pc += 2;
+    /// Switching resolution clears the screen, unless the quirk asks to scale it.
+    fn set_resolution(&mut self, hires: bool) {
+        if !self.quirks.keep_screen_on_resolution_switch {
+            self.hires = hires;
+            self.clear_planes();
+            return;
+        }
+
+        if hires && !self.hires {
+            for y in (0..32).rev() {
+                for x in (0..64).rev() {
+                    let pixel = self.screen[y][x];
+                    self.screen[2 * y][2 * x] = pixel;
+                    self.screen[2 * y][2 * x + 1] = pixel;
+                    self.screen[2 * y + 1][2 * x] = pixel;
+                    self.screen[2 * y + 1][2 * x + 1] = pixel;
                 }
             }
-
-            Instruction::SkipNotPressed(register) => {
-                let key_to_check = Self::keypad_to_keyboard(self.v[register as usize]);
-                if !is_key_down(key_to_check) {
-                    self.pc += 2;
+        } else if !hires && self.hires {
+            for y in 0..32 {
+                for x in 0..64 {
+                    self.screen[y][x] = self.screen[2 * y][2 * x];
                 }
             }
+            // keep the unused part blank, like after a clear
+            for (y, row) in self.screen.iter_mut().enumerate() {
+                let start = if y < 32 { 64 } else { 0 };
+                row[start..].fill(0);
+            }
+        }
+        self.hires = hires;
+        self.screen_update = true;
+    }
 
-            Instruction::Jump(address) => {
-                self.pc = address;
+    /// Moves the display contents `right` and `down` pixels in the current
+    /// resolution. Whatever is pushed off screen is lost, the pixels scrolled
+    /// in are dark.
+    ///
+    /// Every scroll opcode moves the screen right when it runs, nothing is
+    /// batched up until the frame ends. Scrolling down and then right gives
+    /// the same picture as the other way around, except pixels that went
+    /// off screen on the way don't come back.
+    fn scroll(&mut self, right: isize, down: isize) {
+        let (width, height) = (self.width(), self.height());
+        let old = self.screen;
+        for (y, row) in self.screen[..height].iter_mut().enumerate() {
+            for (x, pixel) in row[..width].iter_mut().enumerate() {
+                let source_x = x.checked_add_signed(-right).filter(|x| *x < width);
+                let source_y = y.checked_add_signed(-down).filter(|y| *y < height);
+                let moved = match (source_x, source_y) {
+                    (Some(x), Some(y)) => old[y][x],
+                    _ => 0,
+                };
+                *pixel = (*pixel & !self.planes) | (moved & self.planes);
             }
+        }
+        self.screen_update = true;
+    }
 
-            Instruction::Return => {
-                assert!(self.stack.len() != 0);
-                self.pc = self.stack.pop().unwrap();
+    /// Clears the selected planes for 00E0. The whole buffer is cleared,
+    /// whatever the resolution, so nothing comes back when switching with
+    /// the scaling quirk.
+    fn clear_planes(&mut self) {
+        if self.planes == 0b11 {
+            self.screen = [[0; 128]; 64];
+            return;
+        }
+        let planes = self.planes;
+        let pixels = self.screen.iter_mut().flatten();
+        pixels.for_each(|pixel| *pixel &= !planes);
+    }
+
+    /// The VIP's 8XY1, 8XY2 and 8XY3 leave VF at 0, see `Quirks`.
+    fn reset_vf_on_logic(&mut self) {
+        if self.quirks.reset_vf_on_logic {
+            self.v[0xF] = 0;
+        }
+    }
+
+    /// Moves I past the `count` registers FX55 or FX65 just copied, if the
+    /// quirk says so.
+    fn advance_index(&mut self, count: usize) {
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(count as u16);
+        }
+    }
+
+    /// Runs `instruction`, failing instead of panicking when a ROM does
+    /// something that can't be done, like returning with an empty stack or
+    /// touching memory past the end of RAM.
+    fn exec(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        match instruction {
+            Instruction::Nop => (),
+
+            Instruction::Clear => self.clear_planes(),
+
+            Instruction::LoadNormalRegister(register, value) => {
+                self.v[register as usize] = value;
             }
 
-            Instruction::SubRoutine(address) => {
-                self.stack.push(self.pc);
-                self.pc = address as usize;
+            Instruction::LoadIndexRegister(value) => {
+                self.i = value;
             }
 
-            Instruction::StoreDeelayInRegister(register) => {
-                self.v[register as usize] = self.deelay;
+            Instruction::SkipOnXeqV(register, value) => {
+                self.pc += 2 * (self.v[register as usize] == value) as usize;
             }
 
-            Instruction::SetDeelayFromRegister(register) => {
-                self.deelay = self.v[register as usize];
+            Instruction::SkipOnXneqV(register, value) => {
+                self.pc += 2 * (self.v[register as usize] != value) as usize;
             }
 
-            Instruction::SetSoundTimerFromRegister(register) => {
-                self.sound_timer = self.v[register as usize];
+            Instruction::SkipOnXeqY(x_register, y_register) => {
+                self.pc += 2 * (self.v[x_register as usize] == self.v[y_register as usize]) as usize;
             }
 
-            Instruction::AddRegisterToIndex(register) => {
-                self.i += self.v[register as usize] as u16;
+            Instruction::SkipOnXneqY(x_register, y_register) => {
+                self.pc += 2 * (self.v[x_register as usize] != self.v[y_register as usize]) as usize;
             }
 
-            Instruction::FillRegisters(last_register) => {
-                for register in 0..=last_register {
-                    self.v[register as usize] = self.ram[self.i as usize + register as usize];
-                }
-                self.i = self.i + last_register as u16 + 1;
+            Instruction::SetXtoY(x_register, y_register) => {
+                self.v[x_register as usize] = self.v[y_register as usize];
             }
 
-            Instruction::StoreRegistersInMemmory(last_register) => {
-                for register in 0..=last_register {
-                    self.ram[self.i as usize + register as usize] = self.v[register as usize];
-                }
-                self.i = self.i + last_register as u16 + 1;
+            Instruction::SetXtoXorY(x_register, y_register) => {
+                self.v[x_register as usize] |= self.v[y_register as usize];
+                self.reset_vf_on_logic();
             }
 
-            Instruction::StoreRegisterInBCD(register) => {
-                self.ram[self.i as usize] = self.v[register as usize] / 100;
-                self.ram[self.i as usize + 1] = self.v[register as usize] % 100 / 10;
-                self.ram[self.i as usize + 2] = self.v[register as usize] % 10;
+            Instruction::SetXtoXandY(x_register, y_register) => {
+                self.v[x_register as usize] &= self.v[y_register as usize];
+                self.reset_vf_on_logic();
             }
-        }
-    }
 
-    fn run_opcode(&mut self, opcode: u16) {
-        match opcode & 0xF000 {
-            0x0000 => {
-                if opcode & 0x0FFF == 0x00E0 {
-                    self.exec(Instruction::Clear);
-                } else if opcode & 0x0FFF == 0x00EE {
-                    self.exec(Instruction::Return);
-                } else {
-                    println!("Ignored");
-                }
+            Instruction::SetXtoXxorY(x_register, y_register) => {
+                self.v[x_register as usize] ^= self.v[y_register as usize];
+                self.reset_vf_on_logic();
             }
 
-            0x1000 => {
-                let address = (opcode & 0x0FFF) as usize;
-                self.exec(Instruction::Jump(address));
+            Instruction::AddYtoX(x_register, y_register) => {
+                let mut value: u16 = self.v[x_register as usize] as u16;
+                value += self.v[y_register as usize] as u16;
+
+                self.v[x_register as usize] = value as u8;
+
+                // Set VF to 01 if a carry occurs, else 00
+                self.v[0xF] = 0;
+                if value > 0xFF {
+                    self.v[0xF] = 1;
+                }
             }
 
-            0x2000 => {
-                let address = (opcode & 0x0FFF) as usize;
-                self.exec(Instruction::SubRoutine(address));
+            Instruction::SubYfromX(x_register, y_register) => {
+                let (value, overflowed) =
+                    self.v[x_register as usize].overflowing_sub(self.v[y_register as usize]);
+
+                self.v[x_register as usize] = value;
+
+                // Set VF to 00 if a borrow occurs, else 01
+                self.v[0xF] = 1;
+                if overflowed {
+                    self.v[0xF] = 0;
+                }
             }
 
-            0x3000 => {
-                let register: u8 = (opcode >> 8 & 0x000F) as u8;
-                let value: u8 = (opcode & 0x00FF) as u8;
-                self.exec(Instruction::SkipOnXeqV(register, value));
+            // VF is written last so the flag wins when X is F
+            Instruction::SetXtoYshiftRightOnce(x_register, y_register) => {
+                let source = if self.quirks.shift_uses_vy {
+                    y_register
+                } else {
+                    x_register
+                };
+                let value = self.v[source as usize];
+                self.v[x_register as usize] = value >> 1;
+                self.v[0xF] = value & (1 << 0);
             }
 
-            0x4000 => {
-                let register: u8 = (opcode >> 8 & 0x000F) as u8;
-                let value: u8 = (opcode & 0x00FF) as u8;
-                self.exec(Instruction::SkipOnXneqV(register, value));
+            Instruction::SetXtoYshiftLeftOnce(x_register, y_register) => {
+                let source = if self.quirks.shift_uses_vy {
+                    y_register
+                } else {
+                    x_register
+                };
+                let value = self.v[source as usize];
+                self.v[x_register as usize] = value << 1;
+                self.v[0xF] = value >> 7 & 1;
             }
 
-            0x5000 => {
-                let x_register: u8 = (opcode >> 8 & 0x000F) as u8;
-                let y_register: u8 = (opcode >> 4 & 0x000F) as u8;
-                let value: u8 = (opcode & 0x000F) as u8;
+            Instruction::SetXtoYMinusX(x_register, y_register) => {
+                let (value, overflowed) =
+                    self.v[y_register as usize].overflowing_sub(self.v[x_register as usize]);
+
+                self.v[x_register as usize] = value;
 
-                if value == 0 {
-                    self.exec(Instruction::SkipOnXeqY(x_register, y_register));
+                // Set VF to 00 if a borrow occurs, else 01
+                self.v[0xF] = 1;
+                if overflowed {
+                    self.v[0xF] = 0;
                 }
             }
 
-            0x6000 => {
-                let register: u8 = (opcode >> 8 & 0x000F) as u8;
-                let value: u8 = (opcode & 0x00FF) as u8;
-                self.exec(Instruction::LoadNormalRegister(register, value));
+            Instruction::AddToNormalRegister(register, value) => {
+                let value: u8 = (self.v[register as usize] as u16 + value as u16) as u8;
+                self.v[register as usize] = value;
             }
 
-            0x7000 => {
-                let register: u8 = (opcode >> 8 & 0x000F) as u8;
-                let value: u8 = (opcode & 0x00FF) as u8;
-                self.exec(Instruction::AddToNormalRegister(register, value));
+            Instruction::JumpByRegister(register, address) => {
+                let register = if self.quirks.jump_with_vx {
+                    register
+                } else {
+                    0
+                };
+                self.pc = address + self.v[register as usize] as usize;
             }
 
-            0x8000 => {
-                let x_register: u8 = (opcode >> 8 & 0x000F) as u8;
-                let y_register: u8 = (opcode >> 4 & 0x000F) as u8;
-                let op: u8 = (opcode & 0x000F) as u8;
-
-                match op {
-                    0x0 => {
-                        self.exec(Instruction::SetXtoY(x_register, y_register));
-                    }
+            Instruction::LoadRegisterWithRandom(register, value) => {
+                let random_value = self.random_byte();
+                self.v[register as usize] = value & random_value;
+            }
 
-                    0x1 => {
-                        self.exec(Instruction::SetXtoXorY(x_register, y_register));
+            Instruction::DrawSprite(x_register, y_register, num_bytes) => {
+                let (width, height) = (self.width(), self.height());
+                let x_start = self.v[x_register as usize] as usize % width;
+                let y_start = self.v[y_register as usize] as usize % height;
+                self.v[0xF] = 0;
+                let mut over_edge = false;
+                self.last_collision_count = 0;
+
+                // DXY0 is a SCHIP 16x16 sprite, 2 bytes per row
+                let (rows, sprite_width) = match num_bytes {
+                    0 if self.hires || self.quirks.lores_wide_sprites => (16, 16),
+                    0 => (16, 8),
+                    rows => (rows as usize, 8),
+                };
+
+                // with both planes selected the sprite for the second
+                // plane follows the one for the first
+                let sprite_len = rows * sprite_width / 8;
+                let planes = [1, 2].into_iter().filter(|plane| self.planes & plane != 0);
+                for (index, plane) in planes.enumerate() {
+                    let sprite = self.i as usize + index * sprite_len;
+                    'rows: for y in 0..rows {
+                        for x in 0..sprite_width {
+                            // like most interpreters we are lenient with sprites that run
+                            // past the end of memory, the missing rows are just not drawn
+                            let address = sprite + y * sprite_width / 8 + x / 8;
+                            let Some(&sprite_data) = self.ram.get(address) else {
+                                break 'rows;
+                            };
+
+                            if !sprite_bit(sprite_data, x) {
+                                continue;
+                            }
+
+                            // only a lit pixel past the edge counts as wrapped or clipped
+                            let (mut screen_x, mut screen_y) = (x + x_start, y + y_start);
+                            if screen_y >= height || screen_x >= width {
+                                over_edge = true;
+                                if self.quirks.clip_sprites {
+                                    continue;
+                                }
+                                (screen_x, screen_y) = (screen_x % width, screen_y % height);
+                            }
+
+                            let pixel = &mut self.screen[screen_y][screen_x];
+                            if *pixel & plane != 0 {
+                                self.v[0xF] = 1;
+                                self.last_collision_count += 1;
+                            }
+                            *pixel ^= plane;
+                        }
                     }
+                }
 
-                    0x2 => {
-                        self.exec(Instruction::SetXtoXandY(x_register, y_register));
-                    }
+                if over_edge && self.report_edge_draws {
+                    let wrapped = !self.quirks.clip_sprites;
+                    self.events.push(Chip8Event::EdgeDraw { wrapped });
+                }
+                self.screen_update = true;
+            }
 
-                    0x3 => {
-                        self.exec(Instruction::SetXtoXxorY(x_register, y_register));
-                    }
+            Instruction::LowResolution => self.set_resolution(false),
 
-                    0x4 => {
-                        self.exec(Instruction::AddYtoX(x_register, y_register));
-                    }
+            Instruction::HighResolution => self.set_resolution(true),
 
-                    0x5 => {
-                        self.exec(Instruction::SubYfromX(x_register, y_register));
-                    }
+            Instruction::ScrollDown(rows) => self.scroll(0, rows as isize),
 
-                    0x6 => {
-                        self.exec(Instruction::SetXtoYshiftRightOnce(x_register, y_register));
-                    }
+            Instruction::ScrollUp(rows) => self.scroll(0, -(rows as isize)),
 
-                    0x7 => {
-                        self.exec(Instruction::SetXtoYMinusX(x_register, y_register));
-                    }
+            Instruction::ScrollRight => self.scroll(4, 0),
 
-                    0xE => {
-                        self.exec(Instruction::SetXtoYshiftLeftOnce(x_register, y_register));
-                    }
+            Instruction::ScrollLeft => self.scroll(-4, 0),
 
-                    _ => (),
+            Instruction::WaitUserInput(register) => {
+                self.waiting_for_key = true;
+                if let Some(key) = self.released_key.take() {
+                    self.v[register as usize] = key;
+                    self.waiting_for_key = false;
+                } else {
+                    self.pc -= 2;
                 }
             }
 
-            0x9000 => {
-                let x_register: u8 = (opcode >> 8 & 0x000F) as u8;
-                let y_register: u8 = (opcode >> 4 & 0x000F) as u8;
-                let value: u8 = (opcode & 0x000F) as u8;
+            // only the low nibble picks the digit, like on the VIP and most
+            // interpreters, so I never ends up outside the font
+            Instruction::LoadFont(register) => {
+                let digit = self.v[register as usize] & 0xF;
+                self.i = 0x50 + 5 * digit as u16;
+            }
 
-                if value == 0 {
-                    self.exec(Instruction::SkipOnXneqY(x_register, y_register));
+            Instruction::SkipIfPressed(register) => {
+                if self.is_key_down(self.v[register as usize]) {
+                    self.pc += 2;
                 }
             }
 
-            0xA000 => {
-                let value = opcode & 0x0FFF;
-                self.exec(Instruction::LoadIndexRegister(value));
+            Instruction::SkipNotPressed(register) => {
+                if !self.is_key_down(self.v[register as usize]) {
+                    self.pc += 2;
+                }
             }
 
-            0xB000 => {
-                let address = opcode & 0x0FFF;
-                self.exec(Instruction::JumpByRegister(address as usize));
+            Instruction::Jump(address) => {
+                self.pc = address;
             }
 
-            0xC000 => {
-                let register: u8 = (opcode >> 8 & 0x000F) as u8;
-                let value: u8 = (opcode & 0x00FF) as u8;
-                self.exec(Instruction::LoadRegisterWithRandom(register, value));
+            Instruction::Return => {
+                if self.sp == 0 {
+                    return Err(Chip8Error::StackUnderflow);
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp];
             }
 
-            0xD000 => {
-                let x_register: u8 = (opcode >> 8 & 0x000F) as u8;
-                let y_register: u8 = (opcode >> 4 & 0x000F) as u8;
-                let num_bytes: u8 = (opcode & 0x000F) as u8;
-
-                self.exec(Instruction::DrawSprite(x_register, y_register, num_bytes));
+            Instruction::SubRoutine(address) => {
+                if self.sp == self.stack.len() {
+                    return Err(Chip8Error::StackOverflow);
+                }
+                self.stack[self.sp] = self.pc;
+                self.sp += 1;
+                self.pc = address;
             }
 
-            0xE000 => {
-                let register: u8 = (opcode >> 8 & 0x000F) as u8;
-
-                let sub_opcode = opcode & 0x00FF;
-                match sub_opcode {
-                    0xA1 => {
-                        self.exec(Instruction::SkipNotPressed(register));
-                    }
+            Instruction::StoreDeelayInRegister(register) => {
+                self.v[register as usize] = self.deelay;
+            }
 
-                    0x9E => {
-                        self.exec(Instruction::SkipIfPressed(register));
-                    }
+            Instruction::SetDeelayFromRegister(register) => {
+                self.deelay = self.v[register as usize];
+            }
 
-                    _ => eprintln!("Unsupported key pressed instruction: {:04X}", opcode),
-                }
+            Instruction::SetSoundTimerFromRegister(register) => {
+                self.sound_timer = self.v[register as usize];
+                self.beep_frames = match self.sound_timer {
+                    0 => 0,
+                    frames => frames.max(self.min_beep_frames),
+                };
             }
 
-            0xF000 => {
-                let value: u8 = (opcode >> 8 & 0x000F) as u8;
+            Instruction::AddRegisterToIndex(register) => {
+                self.i = self.i.wrapping_add(self.v[register as usize] as u16);
+            }
 
-                let sub_opcode = opcode & 0x00FF;
-                match sub_opcode {
-                    0x07 => {
-                        self.exec(Instruction::StoreDeelayInRegister(value));
-                    }
+            Instruction::FillRegisters(last_register) => {
+                let count = last_register as usize + 1;
+                let range = self.ram_range(self.i as usize, count)?;
+                self.v[..count].copy_from_slice(&self.ram[range]);
+                self.advance_index(count);
+            }
 
-                    0x0A => {
-                        self.exec(Instruction::WaitUserInput(value));
-                    }
+            Instruction::StoreRegistersInMemmory(last_register) => {
+                let count = last_register as usize + 1;
+                let range = self.ram_range(self.i as usize, count)?;
+                self.ram[range].copy_from_slice(&self.v[..count]);
+                self.advance_index(count);
+            }
 
-                    0x15 => {
-                        self.exec(Instruction::SetDeelayFromRegister(value));
-                    }
+            Instruction::StoreFlags(last_register) => {
+                let count = last_register as usize + 1;
+                self.rpl_flags[..count].copy_from_slice(&self.v[..count]);
+            }
 
-                    0x18 => {
-                        self.exec(Instruction::SetSoundTimerFromRegister(value));
-                    }
+            Instruction::LoadFlags(last_register) => {
+                let count = last_register as usize + 1;
+                self.v[..count].copy_from_slice(&self.rpl_flags[..count]);
+            }
 
-                    0x1E => {
-                        self.exec(Instruction::AddRegisterToIndex(value));
-                    }
+            Instruction::StoreRegisterInBCD(register) => {
+                let range = self.ram_range(self.i as usize, 3)?;
+                let value = self.v[register as usize];
+                self.ram[range].copy_from_slice(&[value / 100, value % 100 / 10, value % 10]);
+            }
 
-                    0x29 => {
-                        self.exec(Instruction::LoadFont(value));
-                    }
+            Instruction::LoadAudioPattern => {
+                let range = self.ram_range(self.i as usize, 16)?;
+                self.audio_pattern.copy_from_slice(&self.ram[range]);
+            }
 
-                    0x33 => {
-                        self.exec(Instruction::StoreRegisterInBCD(value));
-                    }
+            Instruction::SelectPlanes(planes) => self.planes = planes & 0b11,
 
-                    0x55 => {
-                        self.exec(Instruction::StoreRegistersInMemmory(value));
-                    }
+            Instruction::SetPitchFromRegister(register) => {
+                self.audio_pitch = self.v[register as usize];
+            }
+        }
+        Ok(())
+    }
 
-                    0x65 => {
-                        self.exec(Instruction::FillRegisters(value));
-                    }
+    /// Runs `opcode`, returning the instruction it decoded to unless it was
+    /// handled by an override or isn't known. An instruction that fails
+    /// leaves its error in `fault`.
+    fn run_opcode(&mut self, opcode: u16) -> Option<Instruction> {
+        if let Some(index) = self
+            .overrides
+            .iter()
+            .position(|o| opcode & o.mask == o.pattern)
+        {
+            // the handler needs the whole machine, so it's taken out while it runs
+            let mut overrides = core::mem::take(&mut self.overrides);
+            (overrides[index].handler)(self, opcode);
+            overrides.append(&mut self.overrides);
+            self.overrides = overrides;
+            return None;
+        }
 
-                    _ => eprintln!(
-                        "Unsupported instruction found: {:04X} with subopcode: {:02X}",
-                        opcode, sub_opcode
-                    ),
+        let instruction = decode(opcode);
+        match instruction {
+            Some(instruction) => {
+                if self.track_uninit_registers {
+                    self.track_registers(instruction);
+                }
+                if let Err(err) = self.exec(instruction) {
+                    self.fault = Some(err);
                 }
             }
-
-            _ => eprintln!("Unsupported instruction found: {:04X}", opcode),
+            None => {
+                warn!("Unsupported instruction found: {:04X}", opcode);
+                self.unknown_opcodes += 1;
+            }
         }
+        instruction
     }
 
     pub fn start_cycle(&mut self) {
-        let opcode: u16 = (u16::from(self.ram[self.pc]) << 8) + u16::from(self.ram[self.pc + 1]);
-        self.pc += 2;
+        self.step();
+    }
 
-        self.run_opcode(opcode);
+    /// Runs up to `n` instructions without touching the timers, stopping
+    /// early when paused or on a fault, which is returned. Returns how many
+    /// ran.
+    ///
+    /// Every DXYN in there draws right away. A display wait quirk, where a
+    /// draw holds the CPU until the next 60hz frame, would have to end the
+    /// batch at the first draw, the way `run_frame` stops on FX0A.
+    pub fn step_cycles(&mut self, n: usize) -> Result<usize, Chip8Error> {
+        let mut ran = 0;
+        while ran < n && self.step().is_some() {
+            ran += 1;
+        }
+        match &self.fault {
+            Some(fault) => Err(fault.clone()),
+            None => Ok(ran),
+        }
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    fn check_display_activity(&mut self, instruction: Option<Instruction>) {
+        if instruction.is_some_and(Instruction::uses_display) {
+            self.last_display_cycle = self.cycles;
+            self.no_display_reported = false;
+            return;
+        }
 
-    #[test]
-    fn store_num_in_vx() {
-        let mut emulator = Chip8::new(String::from("roms/blank.ch8"));
-        emulator.run_opcode(0x60FE);
-        assert_eq!(emulator.v[0x0], 254);
+        let idle = self.cycles - self.last_display_cycle;
+        let threshold = self.no_display_threshold;
+        if threshold > 0 && idle >= threshold && !self.no_display_reported {
+            let event = Chip8Event::NoDisplayActivity { cycles: idle };
+            self.events.push(event);
+            self.no_display_reported = true;
+        }
     }
 
-    #[test]
-    fn fill_registers() {
-        let mut emulator = Chip8::new(String::from("roms/blank.ch8"));
-        emulator.run_opcode(0xAABC); // I = 2748
-
-        for i in 0..=10 {
-            emulator.ram[0xABC + i as usize] = 11 - i;
+    /// Executes one instruction and reports what it did, `None` while paused
+    /// or once the machine stopped on a fault, see `fault`.
+    pub fn step(&mut self) -> Option<StepOutcome> {
+        if self.paused || self.fault.is_some() {
+            return None;
         }
 
-        emulator.run_opcode(0xFA65); // fill registers
+        self.apply_input_script();
 
-        for i in 0..=10 {
-            emulator.v[i as usize] = 11 - i;
+        if self.latch_keys || self.key_sample_granularity == KeySampleGranularity::PerInstruction {
+            self.keys = self.key_input;
+            self.latch_keys = false;
         }
 
-        assert_eq!(emulator.i, 2748 + 10 + 1)
-    }
-
-    #[test]
-    fn load_registers_in_memmory() {
-        let mut emulator = Chip8::new(String::from("roms/blank.ch8"));
-        emulator.run_opcode(0xAABC); // I = 2748
+        // a jump can send PC anywhere, BNNN past the end of RAM even
+        if let Err(err) = self.ram_range(self.pc, 2) {
+            self.fault = Some(err);
+            return None;
+        }
+
+        // Opcodes are always fetched straight from RAM, nothing is cached.
+        // ROMs that rewrite their own code (e.g. through FX55) rely on this.
+        let opcode: u16 = (u16::from(self.ram[self.pc]) << 8) + u16::from(self.ram[self.pc + 1]);
+        let [pc_high, pc_low] = (self.pc as u16).to_be_bytes();
+        let [op_high, op_low] = opcode.to_be_bytes();
+        self.signature = fnv1a(self.signature, &[pc_high, pc_low, op_high, op_low]);
+        let pc = self.pc;
+        self.pc += 2;
+        self.cycles += 1;
+
+        self.last_opcode = opcode;
+        self.last_instruction = decode(opcode);
+        #[cfg(feature = "std")]
+        let before = (self.v, self.i);
+        let unknown = self.unknown_opcodes;
+        let instruction = self.run_opcode(opcode);
+        if self.unknown_opcodes > unknown {
+            self.note_unknown_opcode(pc, opcode);
+        }
+        self.check_display_activity(instruction);
+        if self.report_busy_waits {
+            self.check_busy_wait(pc, instruction);
+        }
+        let blocked = self.waiting_for_key;
+        let outcome = StepOutcome {
+            pc,
+            opcode,
+            instruction,
+            branched: !blocked && self.pc != pc + 2,
+            collision: matches!(instruction, Some(Instruction::DrawSprite(..))) && self.v[0xF] == 1,
+            blocked,
+        };
+        #[cfg(feature = "std")]
+        if self.trace_file.is_some() {
+            self.write_trace(&outcome, before);
+        }
+        Some(outcome)
+    }
+
+    /// Runs one 60hz frame worth of instructions, then counts the timers down.
+    ///
+    /// A frame that gets stuck on FX0A ends right there, so a machine waiting
+    /// for a key runs one instruction per frame instead of the whole budget.
+    ///
+    /// Fails with the `fault` once an instruction couldn't run, and keeps
+    /// failing until the machine is reset.
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        if self.paused {
+            return Ok(());
+        }
+
+        for _ in 0..self.cycles_per_frame {
+            // nothing changes until the next frame brings new input, no
+            // need to run FX0A over and over
+            if self.step().is_some_and(|outcome| outcome.blocked) {
+                break;
+            }
+        }
+        if let Some(fault) = &self.fault {
+            return Err(fault.clone());
+        }
+        self.tick();
+        self.frames += 1;
+
+        if self.stack_history_len > 0 {
+            self.stack_history.push(self.sp);
+            self.trim_stack_history();
+        }
+        Ok(())
+    }
+
+    /// Steps until the screen actually looks different, which a draw that
+    /// XORs nothing or undoes an earlier one doesn't count as. Gives up after
+    /// `max_cycles` instructions and returns whether the screen changed.
+    ///
+    /// Like `step` this doesn't count the timers down, and a paused machine
+    /// doesn't run at all.
+    pub fn run_until_display_change(&mut self, max_cycles: usize) -> Result<bool, Chip8Error> {
+        let hash = self.framebuffer_hash();
+        for _ in 0..max_cycles {
+            let Some(outcome) = self.step() else {
+                break;
+            };
+            if let Some(fault) = &self.fault {
+                return Err(fault.clone());
+            }
+            let uses_display = outcome.instruction.is_some_and(Instruction::uses_display);
+            if uses_display && self.framebuffer_hash() != hash {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn run_frames(&mut self, frames: u64) -> Result<(), Chip8Error> {
+        for _ in 0..frames {
+            self.run_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Lets `dt` of wall time pass, running every 60hz frame that fits in it
+    /// together with the time left over from earlier calls. Returns how many
+    /// frames ran.
+    ///
+    /// Time spent paused doesn't count.
+    pub fn advance(&mut self, dt: Duration) -> Result<u32, Chip8Error> {
+        const SECOND: u128 = 1_000_000_000;
+
+        if self.paused {
+            return Ok(0);
+        }
+
+        self.pending_time += dt.as_nanos() * 60;
+        let mut frames = 0;
+        while self.pending_time >= SECOND {
+            self.pending_time -= SECOND;
+            self.run_frame()?;
+            frames += 1;
+        }
+        Ok(frames)
+    }
+
+    /// Frames run since the last reset.
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// Instructions executed since the last reset.
+    pub fn cycle_count(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Opcodes skipped since the last reset because they aren't instructions,
+    /// including 8XY_, 5XY_ and 9XY_ with a low nibble that means nothing.
+    pub fn unknown_opcode_count(&self) -> u64 {
+        self.unknown_opcodes
+    }
+
+    /// What stopped the machine, e.g. a return with an empty stack or a
+    /// register load from past the end of RAM. `step` does nothing and
+    /// `run_frame` fails while it's set, a reset clears it.
+    pub fn fault(&self) -> Option<&Chip8Error> {
+        self.fault.as_ref()
+    }
+
+    /// The last few different unknown opcodes `step` ran into, as
+    /// (address, opcode) and the oldest first. Together with
+    /// `required_features` it tells which extensions a ROM wanted.
+    pub fn recent_unknown_opcodes(&self) -> &[(usize, u16)] {
+        &self.recent_unknown
+    }
+
+    fn note_unknown_opcode(&mut self, pc: usize, opcode: u16) {
+        if self.recent_unknown.contains(&(pc, opcode)) {
+            return;
+        }
+        if self.recent_unknown.len() == RECENT_UNKNOWN_LEN {
+            self.recent_unknown.remove(0);
+        }
+        self.recent_unknown.push((pc, opcode));
+    }
+
+    /// The opcode `step` fetched last, or is running right now when asked
+    /// from an override, 0 before the first step.
+    pub fn last_opcode(&self) -> u16 {
+        self.last_opcode
+    }
+
+    /// What `last_opcode` decodes to, `None` if it isn't an instruction. An
+    /// error report can say e.g. "failed while executing DRW V0, V1, 5".
+    pub fn last_instruction(&self) -> Option<Instruction> {
+        self.last_instruction
+    }
+
+    /// Keeps the call stack depth at the end of the last `len` frames run by
+    /// `run_frame`, 0 (the default) turns the recording off.
+    pub fn set_stack_depth_history_len(&mut self, len: usize) {
+        self.stack_history_len = len;
+        self.trim_stack_history();
+    }
+
+    fn trim_stack_history(&mut self) {
+        let len = self.stack_history.len();
+        let excess = len.saturating_sub(self.stack_history_len);
+        self.stack_history.drain(..excess);
+    }
+
+    /// Call stack depth recorded after each frame, oldest first.
+    pub fn stack_depth_history(&self) -> &[usize] {
+        &self.stack_history
+    }
+
+    /// Hash of every (pc, opcode) pair executed since the last reset, in order.
+    ///
+    /// Two runs of the same ROM only end up with the same signature if they
+    /// went down the same path, e.g. to check whether a quirk makes a difference.
+    pub fn execution_signature(&self) -> u64 {
+        self.signature
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blank_machine() {
+        let mut emulator = Chip8::default();
+        assert_eq!(emulator.pc, 0x200);
+        assert!(emulator.ram[0x200..].iter().all(|byte| *byte == 0));
+        assert_eq!(emulator.ram[0x50..0x55], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+
+        emulator.run_opcode(0x6A2A); // VA = 0x2A
+        assert_eq!(emulator.v[0xA], 0x2A);
+    }
+
+    #[test]
+    fn machine_from_opcodes() {
+        let mut emulator = Chip8::from_opcodes(&[0x6005, 0x7003]);
+        assert_eq!(emulator.ram[0x200..0x204], [0x60, 0x05, 0x70, 0x03]);
+        emulator.start_cycle();
+        emulator.start_cycle();
+        assert_eq!(emulator.v[0], 8);
+    }
+
+    #[test]
+    fn store_num_in_vx() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0x60FE);
+        assert_eq!(emulator.v[0x0], 254);
+    }
+
+    #[test]
+    fn fill_registers() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0xAABC); // I = 2748
+
+        for i in 0..=10 {
+            emulator.ram[0xABC + i as usize] = 11 - i;
+        }
+
+        emulator.ram[0xABC + 11] = 0xFF;
+        emulator.run_opcode(0xFA65); // fill registers
+
+        for i in 0..=10 {
+            assert_eq!(emulator.v[i as usize], 11 - i);
+        }
+        assert_eq!(emulator.v[11], 0);
+
+        assert_eq!(emulator.i, 2748 + 10 + 1)
+    }
+
+    #[test]
+    fn load_registers_in_memmory() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0xAABC); // I = 2748
 
         emulator.run_opcode(0x600B); // V0 = 11
         emulator.run_opcode(0x610A); // V1 = 10
@@ -688,25 +2110,67 @@ mod test {
         emulator.run_opcode(0x6902); // V9 = 2
         emulator.run_opcode(0x6A01); // V10 = 1
 
+        emulator.run_opcode(0x6BFF); // V11 = 255, not stored
+
         emulator.run_opcode(0xFA55); // load in memmory
 
         for i in 0..=10 {
-            emulator.ram[emulator.i as usize + i as usize] = 11 - i;
+            assert_eq!(emulator.ram[0xABC + i as usize], 11 - i);
         }
+        assert_eq!(emulator.ram[0xABC + 11], 0);
 
         assert_eq!(emulator.i, 2748 + 10 + 1)
     }
 
+    #[test]
+    fn load_store_index_quirk() {
+        for load_store_increments_i in [true, false] {
+            let mut emulator = Chip8::blank();
+            emulator.set_quirks(Quirks {
+                load_store_increments_i,
+                ..Quirks::default()
+            });
+            emulator.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+            emulator.run_opcode(0xA300);
+            emulator.run_opcode(0xF355); // V0..V3 to 0x300
+            let expected = match load_store_increments_i {
+                true => 0x304,
+                false => 0x300,
+            };
+            assert_eq!(emulator.i, expected);
+
+            emulator.run_opcode(0xA300);
+            emulator.run_opcode(0xF365);
+            assert_eq!(emulator.i, expected);
+            assert_eq!(emulator.ram[0x300..0x304], [1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn jump_by_register_quirk() {
+        for (jump_with_vx, target) in [(false, 0x311), (true, 0x314)] {
+            let mut emulator = Chip8::blank();
+            emulator.set_quirks(Quirks {
+                jump_with_vx,
+                ..Quirks::default()
+            });
+            emulator.v[0] = 0x01;
+            emulator.v[3] = 0x04;
+            emulator.run_opcode(0xB310);
+            assert_eq!(emulator.pc, target);
+        }
+    }
+
     #[test]
     fn load_index() {
-        let mut emulator = Chip8::new(String::from("roms/blank.ch8"));
+        let mut emulator = Chip8::blank();
         emulator.run_opcode(0xAABC);
         assert_eq!(emulator.i, 2748);
     }
 
     #[test]
     fn add_y_to_x_flag_carry() {
-        let mut emulator = Chip8::new(String::from("roms/blank.ch8"));
+        let mut emulator = Chip8::blank();
         emulator.v[0] = 10;
         emulator.v[1] = 255;
         emulator.run_opcode(0x8014);
@@ -715,7 +2179,7 @@ mod test {
 
     #[test]
     fn right_shift_carry() {
-        let mut emulator = Chip8::new(String::from("roms/blank.ch8"));
+        let mut emulator = Chip8::blank();
         emulator.v[1] = 0xFF;
         emulator.run_opcode(0x8016);
 
@@ -726,7 +2190,7 @@ mod test {
 
     #[test]
     fn left_shift_carry() {
-        let mut emulator = Chip8::new(String::from("roms/blank.ch8"));
+        let mut emulator = Chip8::blank();
         emulator.v[1] = 0xFF;
         emulator.run_opcode(0x801E);
 
@@ -737,7 +2201,7 @@ mod test {
 
     #[test]
     fn bcd_test() {
-        let mut emulator = Chip8::new(String::from("roms/blank.ch8"));
+        let mut emulator = Chip8::blank();
         emulator.run_opcode(0x60FE);
         emulator.run_opcode(0xF033);
 
@@ -745,4 +2209,1511 @@ mod test {
         assert_eq!(emulator.ram[emulator.i as usize + 1], 5);
         assert_eq!(emulator.ram[emulator.i as usize + 2], 4);
     }
+
+    #[test]
+    fn bcd_every_value() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0xA300);
+        for value in 0..=255u8 {
+            emulator.v[7] = value;
+            emulator.run_opcode(0xF733);
+            let [hundreds, tens, ones] = [0x300, 0x301, 0x302].map(|a| emulator.ram[a]);
+            assert!(hundreds <= 2 && tens <= 9 && ones <= 9, "{value}");
+            let digits = hundreds as u16 * 100 + tens as u16 * 10 + ones as u16;
+            assert_eq!(digits, u16::from(value));
+        }
+
+        // the last digit would land past the end of RAM
+        emulator.run_opcode(0xAFFE);
+        emulator.run_opcode(0xF733);
+        let out_of_bounds = Chip8Error::MemoryOutOfBounds {
+            address: 0xFFE,
+            len: 3,
+        };
+        assert_eq!(emulator.fault(), Some(&out_of_bounds));
+        assert_eq!(emulator.ram[0xFFE..], [0, 0]);
+    }
+
+    #[test]
+    fn framebuffer_packed_layout() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0xF029); // I = font glyph 0
+        emulator.run_opcode(0x6008); // V0 = 8
+        emulator.run_opcode(0x6102); // V1 = 2
+        emulator.run_opcode(0xD015); // draw 5 rows at (8, 2)
+
+        let packed = emulator.framebuffer_packed();
+        assert_eq!(packed.len(), 256);
+
+        // each row is 8 bytes, the sprite lands in the second byte of rows 2..7
+        assert_eq!(packed[2 * 8 + 1], 0xF0);
+        assert_eq!(packed[3 * 8 + 1], 0x90);
+        assert_eq!(packed[5 * 8 + 1], 0x90);
+        assert_eq!(packed[6 * 8 + 1], 0xF0);
+        assert_eq!(packed[2 * 8], 0x00);
+        assert_eq!(packed.iter().filter(|byte| **byte != 0).count(), 5);
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        let mut emulator = Chip8::blank();
+        let program: [u8; 12] = [
+            0x60, 0x62, // V0 = 0x62
+            0x61, 0x2A, // V1 = 0x2A
+            0xA2, 0x0A, // I = 0x20A
+            0xF1, 0x55, // store V0..V1 at 0x20A, rewriting it to 0x622A
+            0x63, 0x00, // V3 = 0
+            0x00, 0x00, // replaced before it is reached
+        ];
+        emulator.ram[0x200..0x200 + program.len()].copy_from_slice(&program);
+
+        for _ in 0..6 {
+            emulator.start_cycle();
+        }
+
+        assert_eq!(emulator.ram[0x20A], 0x62);
+        assert_eq!(emulator.ram[0x20B], 0x2A);
+        assert_eq!(emulator.v[2], 0x2A);
+    }
+
+    #[test]
+    fn wait_for_key_release() {
+        let mut emulator = Chip8::from_bytes(&[0xF3, 0x0A]); // V3 = wait for key
+        emulator.start_cycle();
+        assert_eq!(emulator.pc, 0x200);
+
+        emulator.set_key(0xB, true);
+        emulator.start_cycle();
+        assert_eq!(emulator.pc, 0x200);
+
+        emulator.set_key(0xB, false);
+        emulator.start_cycle();
+        assert_eq!(emulator.pc, 0x202);
+        assert_eq!(emulator.v[3], 0xB);
+    }
+
+    #[test]
+    fn blocked_frame_stops_early() {
+        let mut emulator = Chip8::from_opcodes(&[0xF30A]); // V3 = wait for key
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.cycle_count(), 1);
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.cycle_count(), 2);
+        assert!(emulator.is_waiting_for_key());
+    }
+
+    #[test]
+    fn run_frame_while_waiting_for_key() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x6A03, // VA = 3
+            0xFA15, // delay timer = VA
+            0xF30A, // V3 = wait for key
+            0x6101, // V1 = 1
+        ]);
+        for _ in 0..3 {
+            emulator.run_frame().unwrap();
+            assert!(emulator.is_waiting_for_key());
+            assert_eq!(emulator.pc, 0x204);
+            assert_eq!(emulator.v[1], 0);
+        }
+        // the rest of the machine keeps going
+        assert_eq!(emulator.frame_count(), 3);
+        assert_eq!(emulator.deelay, 0);
+
+        emulator.set_key(0x7, true);
+        emulator.run_frame().unwrap();
+        emulator.set_key(0x7, false);
+        emulator.run_frame().unwrap();
+        assert!(!emulator.is_waiting_for_key());
+        assert_eq!((emulator.v[3], emulator.v[1]), (0x7, 1));
+    }
+
+    #[test]
+    fn step_outcome() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0xA050, // I = font glyph 0
+            0xD015, // draw it
+            0xD015, // and erase it again
+            0x3000, // skip, V0 is 0
+            0x0000,
+            0xF00A, // wait for key
+        ]);
+        emulator.step();
+
+        let first_draw = emulator.step().unwrap();
+        let draw = Instruction::DrawSprite(0, 1, 5);
+        assert_eq!(first_draw.instruction, Some(draw));
+        assert!(!first_draw.collision);
+
+        let outcome = emulator.step().unwrap();
+        assert_eq!((outcome.pc, outcome.opcode), (0x204, 0xD015));
+        assert!(outcome.collision);
+        assert!(!outcome.branched && !outcome.blocked);
+
+        let skip = emulator.step().unwrap();
+        assert!(skip.branched);
+
+        let wait = emulator.step().unwrap();
+        assert_eq!(wait.instruction, Some(Instruction::WaitUserInput(0)));
+        assert!(wait.blocked && !wait.branched);
+
+        emulator.pause();
+        assert_eq!(emulator.step(), None);
+    }
+
+    #[test]
+    fn input_script() {
+        let mut emulator = Chip8::from_opcodes(&[0x1200]); // loop
+        emulator.set_key_sample_granularity(KeySampleGranularity::PerInstruction);
+        let script = "5:down@3\nA:down@4 5:up@6";
+        emulator.load_input_script(script).unwrap();
+
+        let mut seen = Vec::new();
+        for _ in 0..8 {
+            emulator.step();
+            seen.push((emulator.is_key_down(0x5), emulator.is_key_down(0xA)));
+        }
+        let (up, down) = (false, true);
+        #[rustfmt::skip]
+        assert_eq!(seen, [
+            (up, up), (up, up), (up, up), // cycles 0 - 2
+            (down, up),                   // 3
+            (down, down), (down, down),   // 4, 5
+            (up, down), (up, down),       // 6, 7
+        ]);
+
+        // a reset plays it again
+        emulator.reset();
+        emulator.set_key(0xA, false);
+        for _ in 0..4 {
+            emulator.step();
+        }
+        assert!(emulator.is_key_down(0x5));
+    }
+
+    #[test]
+    fn invalid_input_script() {
+        let mut emulator = Chip8::blank();
+        for (script, entry) in [
+            ("5:down@1 5:sideways@2", 1),
+            ("5:down", 0),
+            ("G:up@3", 0),
+            ("1:up@3 2:up@x", 1),
+            ("1:up@3 2:up@4 12:down@5", 2),
+        ] {
+            let err = emulator.load_input_script(script).unwrap_err();
+            assert_eq!(err, Chip8Error::InvalidInputScript { entry });
+        }
+    }
+
+    #[test]
+    fn draw_smiley_sprite() {
+        let smiley = [
+            0b0011_1100,
+            0b0100_0010,
+            0b1010_0101,
+            0b1000_0001,
+            0b1010_0101,
+            0b1001_1001,
+            0b0100_0010,
+            0b0011_1100,
+        ];
+        let mut emulator = Chip8::blank();
+        emulator.set_sprite(0x300, &smiley).unwrap();
+        emulator.set_index(0x300);
+        emulator.run_opcode(0x6004); // V0 = 4
+        emulator.run_opcode(0x6106); // V1 = 6
+        emulator.run_opcode(0xD018);
+
+        let screen = emulator.framebuffer();
+        for (y, row) in smiley.iter().enumerate() {
+            for x in 0..8 {
+                let expected = (row >> (7 - x)) & 1;
+                assert_eq!(screen[6 + y][4 + x], expected);
+            }
+        }
+        assert_eq!(emulator.v[0xF], 0);
+    }
+
+    #[test]
+    fn screen_as_text() {
+        let mut emulator = Chip8::blank();
+        let sprite = [0b1010_0000, 0b0100_0000];
+        emulator.set_sprite(0x300, &sprite).unwrap();
+        emulator.set_index(0x300);
+        emulator.run_opcode(0xD002);
+
+        let text = emulator.screen_to_string();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert!(lines.iter().all(|line| line.len() == 64));
+        assert!(lines[0].starts_with("#.#."));
+        assert!(lines[1].starts_with(".#.."));
+        assert!(lines[2].chars().all(|c| c == '.'));
+    }
+
+    #[test]
+    fn draw_font_glyph() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0x600A); // V0 = 0xA
+        emulator.run_opcode(0xF029); // I = font glyph A
+        assert_eq!(emulator.i, 0x50 + 5 * 0xA);
+        emulator.run_opcode(0x6100); // V1 = 0
+        emulator.run_opcode(0xD115); // draw 5 rows at (0, 0)
+
+        let glyph = [0xF0, 0x90, 0xF0, 0x90, 0x90];
+        let screen = emulator.framebuffer();
+        for (row, pixels) in glyph.iter().zip(screen) {
+            for (x, pixel) in pixels[..8].iter().enumerate() {
+                assert_eq!(*pixel, (row >> (7 - x)) & 1);
+            }
+        }
+        assert!(screen[5..].iter().flatten().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    fn font_digit_uses_low_nibble() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0x601A); // V0 = 0x1A
+        emulator.run_opcode(0xF029);
+        assert_eq!(emulator.i, 0x50 + 5 * 0xA);
+    }
+
+    #[test]
+    fn collision_count() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0xF029); // glyph 0, 14 pixels
+        emulator.run_opcode(0xD005);
+        assert_eq!(emulator.last_collision_count(), 0);
+        emulator.run_opcode(0xD005);
+        assert_eq!(emulator.last_collision_count(), 14);
+        emulator.run_opcode(0xD005);
+        assert_eq!(emulator.last_collision_count(), 0);
+    }
+
+    #[test]
+    fn select_planes_directly() {
+        let mut emulator = Chip8::blank();
+        assert_eq!(emulator.selected_planes(), 1);
+        emulator.run_opcode(0xF301);
+        assert_eq!(emulator.selected_planes(), 3);
+        let err = emulator.set_selected_planes(4).unwrap_err();
+        assert_eq!(err, Chip8Error::InvalidPlaneMask { mask: 4 });
+        assert_eq!(emulator.selected_planes(), 3);
+
+        emulator.set_selected_planes(2).unwrap();
+        emulator.run_opcode(0xF029);
+        emulator.run_opcode(0xD005);
+        let pixels = || emulator.screen.iter().flatten();
+        assert!(pixels().all(|pixel| *pixel & 1 == 0));
+        assert!(pixels().any(|pixel| *pixel == 2));
+
+        emulator.set_selected_planes(1).unwrap();
+        emulator.run_opcode(0x00E0); // leaves the second plane alone
+        assert!(emulator.screen.iter().flatten().any(|pixel| *pixel == 2));
+        emulator.set_selected_planes(2).unwrap();
+        emulator.run_opcode(0x00E0);
+        assert!(emulator.screen.iter().flatten().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    fn clear_selected_plane() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0xF301); // both planes
+        emulator.run_opcode(0xF029); // glyph 0, then glyph 1 for the second plane
+        emulator.run_opcode(0xD005);
+        let mut pixels = emulator.screen.iter().flatten();
+        assert!(pixels.any(|pixel| *pixel & 2 != 0));
+
+        emulator.run_opcode(0xF201); // second plane only
+        emulator.run_opcode(0x00E0);
+
+        let mut first_plane = Chip8::blank();
+        first_plane.run_opcode(0xF029);
+        first_plane.run_opcode(0xD005);
+        assert_eq!(emulator.screen, first_plane.screen);
+    }
+
+    #[test]
+    fn held_key_repeats() {
+        #[rustfmt::skip]
+        let rom = [
+            0xF0, 0x0A, // wait for a key
+            0x71, 0x01, // V1 += 1
+            0x12, 0x00, // loop
+        ];
+        let mut emulator = Chip8::from_bytes(&rom);
+        emulator.set_key_repeat(3, 2);
+        emulator.set_key(0x5, true);
+
+        let mut counts = Vec::new();
+        for _ in 0..8 {
+            emulator.run_frame().unwrap();
+            counts.push(emulator.v[1]);
+        }
+        assert_eq!(counts, [0, 0, 0, 1, 1, 2, 2, 3]);
+        assert_eq!(emulator.v[0], 0x5);
+
+        // without repeat only a release counts
+        emulator.set_key_repeat(0, 0);
+        emulator.run_frames(8).unwrap();
+        assert_eq!(emulator.v[1], 3);
+        emulator.set_key(0x5, false);
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.v[1], 4);
+    }
+
+    #[test]
+    fn run_until_display_change() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0xA300, // I = 0x300, nothing there
+            0xD005, // draws nothing
+            0xF029, // I = glyph 0
+            0xD005, // draw it
+            0xD005, // and undo it
+            0x120A, // loop
+        ]);
+        assert_eq!(emulator.run_until_display_change(100), Ok(true));
+        assert_eq!(emulator.pc, 0x208);
+        assert_eq!(emulator.run_until_display_change(100), Ok(true));
+        assert_eq!(emulator.pc, 0x20A);
+        assert_eq!(emulator.run_until_display_change(100), Ok(false));
+        assert_eq!(emulator.cycle_count(), 5 + 100);
+    }
+
+    #[test]
+    fn row_bits_match_pixels() {
+        let mut emulator = Chip8::blank();
+        emulator.v[0] = 0xB;
+        emulator.v[1] = 60;
+        emulator.run_opcode(0xF029); // I = glyph B
+        emulator.run_opcode(0xD115); // draw at (60, 60 % 32)
+
+        for y in 28..33 {
+            let bits = emulator.row_bits(y);
+            for x in 0..64 {
+                let lit = bits >> (63 - x) & 1 == 1;
+                assert_eq!(lit, emulator.pixel(x, y), "({x}, {y})");
+            }
+        }
+        // 0xE0 is cut off after 4 pixels by the right edge
+        assert_eq!(emulator.row_bits(28), 0b1110);
+        assert_eq!(emulator.row_bits(32), 0);
+
+        emulator.run_opcode(0x00FF);
+        emulator.v[0] = 124;
+        emulator.run_opcode(0xD005); // glyph B at (124, 124 % 64)
+        assert_eq!(emulator.row_bits(60), 0b1110);
+        assert!(emulator.pixel(126, 60) && !emulator.pixel(127, 60));
+    }
+
+    #[test]
+    fn framebuffer_hash() {
+        let mut emulator = Chip8::blank();
+        let blank = emulator.framebuffer_hash();
+        emulator.run_opcode(0xA050); // I = font 0
+        emulator.run_opcode(0xD005);
+        assert_eq!(emulator.framebuffer_hash(), 0x7B25_88E3_D7CE_C2B5);
+        assert_ne!(emulator.framebuffer_hash(), blank);
+
+        emulator.run_opcode(0xD005);
+        assert_eq!(emulator.framebuffer_hash(), blank);
+    }
+
+    #[test]
+    fn rom_too_large() {
+        let rom = vec![0; 4096 - 0x200 + 1];
+        assert_eq!(
+            Chip8::try_from_bytes(&rom).unwrap_err(),
+            Chip8Error::RomTooLarge {
+                size: rom.len(),
+                max: 4096 - 0x200
+            }
+        );
+        assert!(Chip8::try_from_bytes(&rom[1..]).is_ok());
+    }
+
+    #[test]
+    fn set_sprite_out_of_bounds() {
+        let mut emulator = Chip8::blank();
+        assert_eq!(
+            emulator.set_sprite(0xFFE, &[0xFF; 4]),
+            Err(Chip8Error::MemoryOutOfBounds {
+                address: 0xFFE,
+                len: 4
+            })
+        );
+        assert_eq!(emulator.set_sprite(0xFFC, &[0xFF; 4]), Ok(()));
+    }
+
+    #[test]
+    fn xo_chip_audio() {
+        let mut emulator = Chip8::blank();
+        assert_eq!(emulator.audio_pitch(), 64);
+
+        let pattern: [u8; 16] = core::array::from_fn(|i| i as u8 * 0x11);
+        emulator.set_sprite(0x400, &pattern).unwrap();
+        emulator.set_index(0x400);
+        emulator.run_opcode(0xF002);
+        assert_eq!(emulator.audio_pattern(), &pattern);
+
+        emulator.run_opcode(0x6570); // V5 = 0x70
+        emulator.run_opcode(0xF53A);
+        assert_eq!(emulator.audio_pitch(), 0x70);
+    }
+
+    #[test]
+    fn reset_restores_rom() {
+        let mut emulator = Chip8::from_bytes(&[0x60, 0x01, 0xA1, 0x23]);
+        emulator.start_cycle();
+        emulator.start_cycle();
+        emulator.pause();
+        emulator.reset();
+
+        assert_eq!(emulator.pc, 0x200);
+        assert_eq!(emulator.v[0], 0);
+        assert_eq!(emulator.i, 0);
+        assert_eq!(emulator.ram[0x200], 0x60);
+        assert!(emulator.is_paused());
+    }
+
+    #[test]
+    fn warm_reset_keeps_ram() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x6012, // V0 = 0x12
+            0xA200, // I = 0x200
+            0xF055, // overwrite the first instruction
+            0xF075, // and save V0 to the flags
+        ]);
+        for _ in 0..4 {
+            emulator.step();
+        }
+        emulator.warm_reset();
+        assert_eq!(emulator.pc, 0x200);
+        assert_eq!(emulator.v, [0; 16]);
+        assert_eq!(emulator.i, 0);
+        assert_eq!(emulator.ram[0x200], 0x12);
+        assert_eq!(emulator.rpl_flags()[0], 0x12);
+
+        emulator.reset();
+        assert_eq!(emulator.ram[0x200], 0x60);
+    }
+
+    #[test]
+    fn draw_sprite_past_end_of_memory() {
+        let mut emulator = Chip8::blank();
+        emulator.set_sprite(0xFFE, &[0xFF, 0x81]).unwrap();
+        emulator.run_opcode(0xAFFE); // I = 0xFFE
+        emulator.run_opcode(0xD005); // 5 rows, only 2 of them in memory
+
+        let screen = emulator.framebuffer();
+        assert_eq!(screen[0][..8], [1; 8]);
+        assert_eq!(screen[1][..8], [1, 0, 0, 0, 0, 0, 0, 1]);
+        assert!(screen[2..].iter().flatten().all(|pixel| *pixel == 0));
+        assert_eq!(emulator.v[0xF], 0);
+    }
+
+    #[test]
+    fn muted_sound_timer_keeps_counting() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0x6005); // V0 = 5
+        emulator.run_opcode(0xF018); // sound timer = V0
+        assert!(emulator.is_beeping());
+
+        emulator.set_muted(true);
+        emulator.tick();
+        emulator.tick();
+        assert!(!emulator.is_beeping());
+        assert_eq!(emulator.sound_timer, 3);
+
+        emulator.set_muted(false);
+        assert!(emulator.is_beeping());
+    }
+
+    /// Beeping state after FX18 with `value`, then after every following tick.
+    fn beep_for(value: u8) -> Vec<bool> {
+        let mut emulator = Chip8::blank();
+        emulator.v[0] = value;
+        emulator.run_opcode(0xF018); // sound timer = V0
+        let mut beeping = vec![emulator.is_beeping()];
+        for _ in 0..3 {
+            emulator.tick();
+            beeping.push(emulator.is_beeping());
+        }
+        assert_eq!(emulator.sound_timer, 0);
+        beeping
+    }
+
+    #[test]
+    fn timers_reach_zero() {
+        let mut emulator = Chip8::blank();
+        emulator.v[0] = 3;
+        emulator.run_opcode(0xF018);
+        emulator.run_opcode(0xF015);
+        for _ in 0..3 {
+            emulator.tick();
+        }
+        assert_eq!((emulator.sound_timer, emulator.deelay), (0, 0));
+        assert!(!emulator.is_beeping());
+        emulator.tick();
+        assert_eq!((emulator.sound_timer, emulator.deelay), (0, 0));
+    }
+
+    #[test]
+    fn sound_timer_of_zero_is_silent() {
+        assert_eq!(beep_for(0), [false, false, false, false]);
+    }
+
+    #[test]
+    fn sound_timer_of_one_beeps_for_one_tick() {
+        assert_eq!(beep_for(1), [true, false, false, false]);
+    }
+
+    #[test]
+    fn sound_timer_of_two_beeps_for_two_ticks() {
+        assert_eq!(beep_for(2), [true, true, false, false]);
+    }
+
+    #[test]
+    fn short_beep_is_extended() {
+        let mut emulator = Chip8::blank();
+        emulator.set_min_beep_frames(4);
+        emulator.v[0] = 1;
+        emulator.run_opcode(0xF018); // sound timer = V0
+        let mut beeping = vec![emulator.is_beeping()];
+        for _ in 0..4 {
+            emulator.tick();
+            beeping.push(emulator.is_beeping());
+        }
+        assert_eq!(beeping, [true, true, true, true, false]);
+        assert_eq!(emulator.sound_timer, 0);
+    }
+
+    #[test]
+    fn replay_random_values() {
+        #[rustfmt::skip]
+        let rom = [
+            0xC0, 0xFF, // V0 = random
+            0xC1, 0xFF, // V1 = random
+            0xC2, 0x0F, // V2 = random & 0x0F
+        ];
+        let mut emulator = Chip8::from_bytes(&rom);
+        emulator.set_rng_logging(true);
+        for _ in 0..3 {
+            emulator.step();
+        }
+        let log = emulator.rng_log().to_vec();
+        assert_eq!(log[..2], emulator.v[..2]);
+
+        let mut replayed = Chip8::from_bytes(&rom);
+        replayed.replay_rng(vec![0x12, 0x34, 0xAB]);
+        for _ in 0..3 {
+            replayed.step();
+        }
+        assert_eq!(replayed.v[..3], [0x12, 0x34, 0x0B]);
+
+        // a logged run plays back the same
+        replayed.reset();
+        replayed.replay_rng(log);
+        for _ in 0..3 {
+            replayed.step();
+        }
+        assert_eq!(replayed.v, emulator.v);
+
+        // once the values run out the generator takes over again
+        replayed.reset();
+        replayed.replay_rng(vec![0x12]);
+        replayed.step();
+        replayed.step();
+        assert_eq!(replayed.v[..2], [0x12, emulator.v[0]]);
+    }
+
+    #[test]
+    fn step_a_number_of_cycles() {
+        let mut emulator = Chip8::from_opcodes(&[0x7001, 0x1200]);
+        assert_eq!(emulator.step_cycles(11), Ok(11));
+        assert_eq!(emulator.v[0], 6);
+        emulator.pause();
+        assert_eq!(emulator.step_cycles(5), Ok(0));
+        emulator.resume();
+
+        emulator.set_clock_speed(700).unwrap();
+        assert_eq!(emulator.cycles_per_frame(), 12);
+        let err = emulator.set_clock_speed(20).unwrap_err();
+        let reason = "less than one cycle per frame";
+        assert_eq!(err, Chip8Error::InvalidConfig { reason });
+        assert_eq!(emulator.cycles_per_frame(), 12);
+
+        let mut emulator = Chip8::from_opcodes(&[0x6001, 0x00EE, 0x6002]);
+        assert_eq!(emulator.step_cycles(3), Err(Chip8Error::StackUnderflow));
+        assert_eq!(emulator.v[0], 1);
+    }
+
+    #[test]
+    fn count_frames_and_cycles() {
+        let mut emulator = Chip8::from_bytes(&[0x12, 0x00]); // jump to itself
+        emulator.run_frames(10).unwrap();
+        assert_eq!(emulator.frame_count(), 10);
+        assert_eq!(emulator.cycle_count(), 100);
+
+        emulator.pause();
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.frame_count(), 10);
+
+        emulator.reset();
+        assert_eq!(emulator.frame_count(), 0);
+        assert_eq!(emulator.cycle_count(), 0);
+    }
+
+    #[test]
+    fn advance_without_drift() {
+        let mut emulator = Chip8::from_bytes(&[0x12, 0x00]); // loop
+        emulator.deelay = 255;
+
+        let mut dts = vec![Duration::from_millis(16); 5];
+        dts.push(Duration::from_millis(1500)); // a long stall
+        dts.extend([Duration::from_micros(700); 40]);
+        dts.extend([Duration::from_nanos(16_666_666); 30]);
+
+        let mut total = Duration::ZERO;
+        let mut ticks = 0;
+        for dt in dts {
+            total += dt;
+            ticks += emulator.advance(dt).unwrap();
+            // every whole 1/60 s that passed so far has been ticked, no more
+            let expected = total.as_nanos() * 60 / 1_000_000_000;
+            assert_eq!(u128::from(ticks), expected);
+        }
+        assert_eq!(ticks, 126);
+        assert_eq!(emulator.frame_count(), 126);
+        assert_eq!(emulator.deelay, 255 - 126);
+    }
+
+    #[test]
+    fn advance_in_exact_frames() {
+        let mut emulator = Chip8::from_bytes(&[0x12, 0x00]); // loop
+
+        // a third of a frame at a time, the remainder may not get lost
+        let third = Duration::from_nanos(1_000_000_000 / 180);
+        let ticks: Vec<_> = (0..9).map(|_| emulator.advance(third).unwrap()).collect();
+        assert_eq!(ticks, [0, 0, 0, 1, 0, 0, 1, 0, 0]);
+
+        emulator.pause();
+        assert_eq!(emulator.advance(Duration::from_secs(1)).unwrap(), 0);
+        emulator.resume();
+        assert_eq!(emulator.advance(third).unwrap(), 1);
+        assert_eq!(emulator.frame_count(), 3);
+    }
+
+    #[test]
+    fn timers_stop_while_paused() {
+        let mut emulator = Chip8::from_bytes(&[0x12, 0x00]); // loop
+        emulator.run_opcode(0x6028); // V0 = 40
+        emulator.run_opcode(0xF015); // delay timer = V0
+
+        emulator.pause();
+        for _ in 0..5 {
+            emulator.tick();
+            emulator.run_frame().unwrap();
+            emulator.advance(Duration::from_millis(100)).unwrap();
+            emulator.run_opcode(0xF107); // V1 = delay timer
+            assert_eq!(emulator.v[1], 40);
+        }
+
+        emulator.resume();
+        emulator.run_frames(3).unwrap();
+        emulator.run_opcode(0xF107);
+        assert_eq!(emulator.v[1], 37);
+    }
+
+    #[test]
+    fn stack_depth_history() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x2204, // 200: call 204
+            0x1202, // 202: loop
+            0x2208, // 204: call 208
+            0x00EE, // 206: return
+            0x120A, // 208: jump 20A
+            0x00EE, // 20A: return
+        ]);
+        emulator.cycles_per_frame = 1;
+        emulator.run_frames(2).unwrap();
+        assert!(emulator.stack_depth_history().is_empty());
+
+        emulator.reset();
+        emulator.set_stack_depth_history_len(4);
+        emulator.run_frames(6).unwrap();
+        // 1, 2, 2, 1, then 0 while looping, only the last 4 are kept
+        assert_eq!(emulator.stack_depth_history(), [2, 1, 0, 0]);
+
+        emulator.set_stack_depth_history_len(1);
+        assert_eq!(emulator.stack_depth_history(), [0]);
+    }
+
+    #[test]
+    fn zero_padding_is_a_nop() {
+        let mut emulator = Chip8::from_bytes(&[0; 6]);
+        for pc in [0x200, 0x202, 0x204] {
+            let outcome = emulator.step().unwrap();
+            assert_eq!(outcome.pc, pc);
+            assert_eq!(outcome.instruction, Some(Instruction::Nop));
+        }
+        assert_eq!(emulator.pc, 0x206);
+        assert_eq!(emulator.unknown_opcode_count(), 0);
+
+        // other machine code calls are still unknown
+        emulator.run_opcode(0x0123);
+        assert_eq!(emulator.unknown_opcode_count(), 1);
+    }
+
+    #[test]
+    fn base_opcodes_decode() {
+        use Instruction::*;
+        #[rustfmt::skip]
+        let opcodes = [
+            (0x00E0, Clear, "CLS"),
+            (0x00EE, Return, "RET"),
+            (0x1234, Jump(0x234), "JP 0x234"),
+            (0x2345, SubRoutine(0x345), "CALL 0x345"),
+            (0x312A, SkipOnXeqV(1, 0x2A), "SE V1, 0x2A"),
+            (0x4B07, SkipOnXneqV(0xB, 0x07), "SNE VB, 0x07"),
+            (0x5230, SkipOnXeqY(2, 3), "SE V2, V3"),
+            (0x6AFF, LoadNormalRegister(0xA, 0xFF), "LD VA, 0xFF"),
+            (0x7C01, AddToNormalRegister(0xC, 0x01), "ADD VC, 0x01"),
+            (0x8120, SetXtoY(1, 2), "LD V1, V2"),
+            (0x8121, SetXtoXorY(1, 2), "OR V1, V2"),
+            (0x8122, SetXtoXandY(1, 2), "AND V1, V2"),
+            (0x8123, SetXtoXxorY(1, 2), "XOR V1, V2"),
+            (0x8124, AddYtoX(1, 2), "ADD V1, V2"),
+            (0x8125, SubYfromX(1, 2), "SUB V1, V2"),
+            (0x8126, SetXtoYshiftRightOnce(1, 2), "SHR V1, V2"),
+            (0x8127, SetXtoYMinusX(1, 2), "SUBN V1, V2"),
+            (0x812E, SetXtoYshiftLeftOnce(1, 2), "SHL V1, V2"),
+            (0x9EF0, SkipOnXneqY(0xE, 0xF), "SNE VE, VF"),
+            (0xA321, LoadIndexRegister(0x321), "LD I, 0x321"),
+            (0xB400, JumpByRegister(4, 0x400), "JP V0, 0x400"),
+            (0xC50F, LoadRegisterWithRandom(5, 0x0F), "RND V5, 0x0F"),
+            (0xD015, DrawSprite(0, 1, 5), "DRW V0, V1, 5"),
+            (0xE69E, SkipIfPressed(6), "SKP V6"),
+            (0xE7A1, SkipNotPressed(7), "SKNP V7"),
+            (0xF807, StoreDeelayInRegister(8), "LD V8, DT"),
+            (0xF90A, WaitUserInput(9), "LD V9, K"),
+            (0xFA15, SetDeelayFromRegister(0xA), "LD DT, VA"),
+            (0xFB18, SetSoundTimerFromRegister(0xB), "LD ST, VB"),
+            (0xFC1E, AddRegisterToIndex(0xC), "ADD I, VC"),
+            (0xFD29, LoadFont(0xD), "LD F, VD"),
+            (0xFE33, StoreRegisterInBCD(0xE), "LD B, VE"),
+            (0xFF55, StoreRegistersInMemmory(0xF), "LD [I], VF"),
+            (0xF065, FillRegisters(0), "LD V0, [I]"),
+        ];
+        for (opcode, instruction, text) in opcodes {
+            assert_eq!(decode(opcode), Some(instruction), "{opcode:04X}");
+            assert_eq!(encode(instruction), opcode);
+            assert_eq!(instruction.to_string(), text);
+        }
+        // the 35th, 0NNN, calls machine code and can't be run
+        assert_eq!(decode(0x0123), None);
+    }
+
+    #[test]
+    fn last_executed_instruction() {
+        let mut emulator = Chip8::from_opcodes(&[0xD015, 0x800D]);
+        assert_eq!(emulator.last_instruction(), None);
+        emulator.step();
+        assert_eq!(emulator.last_opcode(), 0xD015);
+        let drawn = Some(Instruction::DrawSprite(0, 1, 5));
+        assert_eq!(emulator.last_instruction(), drawn);
+
+        emulator.step();
+        assert_eq!(emulator.last_opcode(), 0x800D);
+        assert_eq!(emulator.last_instruction(), None);
+    }
+
+    #[test]
+    fn stack_faults() {
+        let mut emulator = Chip8::from_opcodes(&[0x00EE, 0x6001]);
+        assert_eq!(emulator.run_frame(), Err(Chip8Error::StackUnderflow));
+        assert_eq!(emulator.fault(), Some(&Chip8Error::StackUnderflow));
+        assert_eq!(emulator.step(), None);
+        assert_eq!(emulator.run_frame(), Err(Chip8Error::StackUnderflow));
+        assert_eq!(emulator.v[0], 0);
+
+        emulator.reset();
+        assert_eq!(emulator.fault(), None);
+
+        // calls itself until the stack is full
+        let mut emulator = Chip8::from_opcodes(&[0x2200]);
+        assert_eq!(emulator.run_frames(2), Err(Chip8Error::StackOverflow));
+        assert_eq!(emulator.cycle_count(), 17);
+    }
+
+    #[test]
+    fn memory_faults() {
+        let past_the_end = |opcodes: &[u16]| {
+            let mut emulator = Chip8::from_opcodes(opcodes);
+            for _ in 0..opcodes.len() {
+                emulator.step();
+            }
+            emulator.fault().cloned()
+        };
+        let out_of_bounds = |address, len| Some(Chip8Error::MemoryOutOfBounds { address, len });
+        assert_eq!(past_the_end(&[0xAFFF, 0xF165]), out_of_bounds(0xFFF, 2));
+        assert_eq!(past_the_end(&[0xAFFE, 0xF055]), None);
+        assert_eq!(past_the_end(&[0xAFFE, 0xF033]), out_of_bounds(0xFFE, 3));
+        assert_eq!(past_the_end(&[0xAFF8, 0xF002]), out_of_bounds(0xFF8, 16));
+        // BNNN and skips can take PC past the end
+        let jump = past_the_end(&[0x60FF, 0xBFFF, 0x0000]);
+        assert_eq!(jump, out_of_bounds(0x10FE, 2));
+
+        // an I that goes past 0xFFFF wraps around instead of overflowing
+        let mut emulator = Chip8::from_opcodes(&[0xAFFF, 0x60FF, 0xF01E, 0x1204]);
+        emulator.run_frames(30).unwrap();
+        assert_eq!(emulator.fault(), None);
+    }
+
+    #[test]
+    fn list_recent_unknown_opcodes() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x800D, // not an instruction
+            0x6001, // V0 = 1
+            0x00FD, // SCHIP exit, not supported
+            0x4001, // loop while V0 is 1
+            0x1200,
+        ]);
+        for _ in 0..10 {
+            emulator.step();
+        }
+        let recent = emulator.recent_unknown_opcodes();
+        assert_eq!(recent, [(0x200, 0x800D), (0x204, 0x00FD)]);
+        assert_eq!(emulator.unknown_opcode_count(), 4);
+
+        emulator.reset();
+        assert!(emulator.recent_unknown_opcodes().is_empty());
+    }
+
+    #[test]
+    fn count_unknown_opcodes() {
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0x800D);
+        assert_eq!(emulator.unknown_opcode_count(), 1);
+        assert_eq!(emulator.v, [0; 16]);
+
+        emulator.run_opcode(0x5121);
+        emulator.run_opcode(0x912F);
+        emulator.run_opcode(0x8124); // known opcodes don't count
+        assert_eq!(emulator.unknown_opcode_count(), 3);
+
+        emulator.reset();
+        assert_eq!(emulator.unknown_opcode_count(), 0);
+    }
+
+    #[test]
+    fn opcode_override() {
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let handler_seen = Arc::clone(&seen);
+        let mut emulator = Chip8::from_bytes(&[0x5AB1, 0x60FF].map(u16::to_be_bytes).concat());
+        emulator.set_opcode_override(
+            0xF00F,
+            0x5001,
+            Box::new(move |chip8, opcode| {
+                handler_seen.lock().unwrap().push(opcode);
+                chip8.v[0xA] = 0x42;
+            }),
+        );
+
+        emulator.start_cycle();
+        emulator.start_cycle();
+        assert_eq!(*seen.lock().unwrap(), [0x5AB1]);
+        assert_eq!(emulator.v[0xA], 0x42);
+        assert_eq!(emulator.v[0], 0xFF);
+        assert_eq!(emulator.pc, 0x204);
+    }
+
+    /// Presses key 0 between the second and third instruction of a frame,
+    /// returns whether the second EX9E saw it.
+    fn key_seen_mid_frame(granularity: KeySampleGranularity) -> bool {
+        let rom = [0xE0, 0x9E, 0x61, 0x01, 0xE0, 0x9E, 0x62, 0x01];
+        let mut emulator = Chip8::from_bytes(&rom);
+        emulator.set_key_sample_granularity(granularity);
+        emulator.start_cycle();
+        emulator.start_cycle();
+        emulator.set_key(0x0, true);
+        emulator.start_cycle();
+        emulator.start_cycle();
+        assert_eq!(emulator.v[1], 1);
+        emulator.v[2] == 0
+    }
+
+    #[test]
+    fn key_sample_granularity() {
+        assert!(!key_seen_mid_frame(KeySampleGranularity::PerFrame));
+        assert!(key_seen_mid_frame(KeySampleGranularity::PerInstruction));
+    }
+
+    #[test]
+    fn key_latched_on_next_frame() {
+        let mut emulator = Chip8::from_bytes(&[0xE0, 0x9E]);
+        emulator.start_cycle();
+        emulator.set_key(0x0, true);
+        assert!(!emulator.is_key_down(0x0));
+
+        emulator.tick();
+        emulator.pc = 0x200;
+        emulator.start_cycle();
+        assert!(emulator.is_key_down(0x0));
+        assert_eq!(emulator.pc, 0x204);
+    }
+
+    /// 32 bytes of sprite data, a diagonal when read as a 16x16 sprite
+    const BIG_SPRITE: [u8; 32] = [
+        0x80, 0x00, 0x40, 0x00, 0x20, 0x00, 0x10, 0x00, 0x08, 0x00, 0x04, 0x00, 0x02, 0x00, 0x01,
+        0x00, 0x00, 0x80, 0x00, 0x40, 0x00, 0x20, 0x00, 0x10, 0x00, 0x08, 0x00, 0x04, 0x00, 0x02,
+        0x00, 0x01,
+    ];
+
+    /// Draws BIG_SPRITE with DXY0 at (0, 0)
+    fn draw_big_sprite(hires: bool, quirks: Quirks) -> [[u8; 128]; 64] {
+        let mut emulator = Chip8::blank();
+        emulator.set_quirks(quirks);
+        if hires {
+            emulator.run_opcode(0x00FF);
+        }
+        emulator.set_sprite(0x300, &BIG_SPRITE).unwrap();
+        emulator.set_index(0x300);
+        emulator.run_opcode(0xD000);
+        *emulator.framebuffer()
+    }
+
+    fn lit_pixels(screen: &[[u8; 128]; 64]) -> Vec<(usize, usize)> {
+        let mut pixels = Vec::new();
+        for (y, row) in screen.iter().enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                if *pixel == 1 {
+                    pixels.push((x, y));
+                }
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn high_res_display() {
+        let mut emulator = Chip8::blank();
+        assert_eq!((emulator.width(), emulator.height()), (64, 32));
+        emulator.run_opcode(0x00FF);
+        assert!(emulator.is_hires());
+        assert_eq!((emulator.width(), emulator.height()), (128, 64));
+
+        // 100 doesn't wrap around anymore
+        emulator.run_opcode(0xF029);
+        emulator.run_opcode(0x6064); // V0 = 100
+        emulator.run_opcode(0x6128); // V1 = 40
+        emulator.run_opcode(0xD011);
+        assert_eq!(emulator.framebuffer()[40][100..104], [1, 1, 1, 1]);
+        assert_eq!(emulator.framebuffer_packed().len(), 1024);
+
+        emulator.run_opcode(0x00FE);
+        assert!(!emulator.is_hires());
+        assert!(lit_pixels(emulator.framebuffer()).is_empty());
+    }
+
+    /// Draws the 0 glyph at (2, 1) in low-res.
+    fn draw_zero(quirks: Quirks) -> Chip8 {
+        let mut emulator = Chip8::blank();
+        emulator.set_quirks(quirks);
+        emulator.run_opcode(0xF029); // I = font glyph 0
+        emulator.run_opcode(0x6002); // V0 = 2
+        emulator.run_opcode(0x6101); // V1 = 1
+        emulator.run_opcode(0xD015);
+        emulator
+    }
+
+    fn switch_to_high_res(quirks: Quirks) -> Chip8 {
+        let mut emulator = draw_zero(quirks);
+        emulator.run_opcode(0x00FF);
+        emulator
+    }
+
+    #[test]
+    fn resolution_switch_clears_screen() {
+        let emulator = switch_to_high_res(Quirks::default());
+        assert!(emulator.is_hires());
+        assert!(lit_pixels(emulator.framebuffer()).is_empty());
+    }
+
+    #[test]
+    fn resolution_switch_keeps_screen() {
+        let quirks = Quirks {
+            keep_screen_on_resolution_switch: true,
+            ..Quirks::default()
+        };
+        let mut emulator = switch_to_high_res(quirks);
+        let screen = emulator.framebuffer();
+        // the top row of the glyph, 4 pixels wide at (2, 1), is now 8x2 at (4, 2)
+        for y in [2, 3] {
+            assert_eq!(screen[y][3..13], [0, 1, 1, 1, 1, 1, 1, 1, 1, 0]);
+        }
+        // its sides are 2 pixels wide
+        assert_eq!(screen[4][3..13], [0, 1, 1, 0, 0, 0, 0, 1, 1, 0]);
+        assert_eq!(lit_pixels(screen).len(), 14 * 4);
+
+        // and going back restores the original
+        emulator.run_opcode(0x00FE);
+        assert_eq!(emulator.framebuffer(), draw_zero(quirks).framebuffer());
+    }
+
+    /// A machine with every pixel of the display lit.
+    fn lit_screen(hires: bool) -> Chip8 {
+        let mut emulator = Chip8::blank();
+        if hires {
+            emulator.run_opcode(0x00FF);
+        }
+        let (width, height) = (emulator.width(), emulator.height());
+        for row in &mut emulator.screen[..height] {
+            row[..width].fill(1);
+        }
+        emulator
+    }
+
+    #[test]
+    fn clear_full_screen() {
+        for hires in [false, true] {
+            let mut emulator = lit_screen(hires);
+            emulator.run_opcode(0x00E0);
+            assert!(emulator.screen.iter().flatten().all(|pixel| *pixel == 0));
+
+            let mut emulator = lit_screen(hires);
+            emulator.run_opcode(0xF301); // both planes
+            emulator.screen[3][5] = 0b11;
+            emulator.run_opcode(0x00E0);
+            assert!(emulator.screen.iter().flatten().all(|pixel| *pixel == 0));
+        }
+    }
+
+    #[test]
+    fn scroll_left_repeatedly() {
+        for hires in [false, true] {
+            let mut emulator = lit_screen(hires);
+            let width = emulator.width();
+            for times in 1..=width / 4 + 2 {
+                emulator.run_opcode(0x00FC);
+                let cleared = (4 * times).min(width);
+                for row in &emulator.framebuffer()[..emulator.height()] {
+                    assert!(row[..width - cleared].iter().all(|pixel| *pixel == 1));
+                    assert!(row[width - cleared..].iter().all(|pixel| *pixel == 0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scroll_right_repeatedly() {
+        let mut emulator = lit_screen(false);
+        for times in 1..=18 {
+            emulator.run_opcode(0x00FB);
+            let cleared = (4 * times).min(64);
+            for row in &emulator.framebuffer()[..32] {
+                assert!(row[..cleared].iter().all(|pixel| *pixel == 0));
+                assert!(row[cleared..64].iter().all(|pixel| *pixel == 1));
+            }
+        }
+    }
+
+    #[test]
+    fn scroll_up_and_down() {
+        let lit_row = |row: &[u8; 128]| row[..64].iter().all(|pixel| *pixel == 1);
+        let mut emulator = lit_screen(false);
+        emulator.run_opcode(0x00C3); // down 3
+        let screen = emulator.framebuffer();
+        assert!(screen[..3].iter().flatten().all(|pixel| *pixel == 0));
+        assert!(screen[3..32].iter().all(lit_row));
+
+        emulator.run_opcode(0x00DF); // up 15
+        let screen = emulator.framebuffer();
+        assert!(screen[..17].iter().all(lit_row));
+        assert!(screen[17..].iter().flatten().all(|pixel| *pixel == 0));
+
+        for _ in 0..4 {
+            emulator.run_opcode(0x00DF);
+        }
+        assert!(lit_pixels(emulator.framebuffer()).is_empty());
+    }
+
+    #[test]
+    fn scroll_down_then_right() {
+        let mut emulator = Chip8::blank();
+        for (x, y) in [(1, 1), (61, 30), (62, 5)] {
+            emulator.screen[y][x] = 1;
+        }
+        emulator.run_opcode(0x00C2); // down 2, (61, 30) drops off the bottom
+        emulator.run_opcode(0x00FB); // right 4, (62, 7) drops off the side
+        assert_eq!(lit_pixels(emulator.framebuffer()), [(5, 3)]);
+
+        // scrolling back doesn't bring anything back
+        emulator.run_opcode(0x00FC);
+        emulator.run_opcode(0x00D2);
+        assert_eq!(lit_pixels(emulator.framebuffer()), [(1, 1)]);
+    }
+
+    #[test]
+    fn edge_draw_events() {
+        let draw_across_right_edge = |quirks: Quirks| {
+            let mut emulator = Chip8::blank();
+            emulator.set_quirks(quirks);
+            emulator.set_report_edge_draws(true);
+            emulator.run_opcode(0xF029); // I = font glyph 0
+            emulator.run_opcode(0x603E); // V0 = 62
+            emulator.run_opcode(0xD115); // draw at (0, 0), fits
+            emulator.run_opcode(0xD015); // draw at (62, 0)
+            emulator
+        };
+
+        let mut clipped = draw_across_right_edge(Quirks::default());
+        let events = clipped.drain_events();
+        assert_eq!(events, [Chip8Event::EdgeDraw { wrapped: false }]);
+        assert_eq!(clipped.framebuffer()[0][..4], [1, 1, 1, 1]);
+        assert!(clipped.drain_events().is_empty());
+
+        let quirks = Quirks {
+            clip_sprites: false,
+            ..Quirks::default()
+        };
+        let mut wrapped = draw_across_right_edge(quirks);
+        let events = wrapped.drain_events();
+        assert_eq!(events, [Chip8Event::EdgeDraw { wrapped: true }]);
+        // the right half of the glyph lands on top of the one at (0, 0)
+        assert_eq!(wrapped.framebuffer()[0][..4], [0, 0, 1, 1]);
+        assert_eq!(wrapped.v[0xF], 1);
+
+        // nothing is reported unless asked for
+        let mut emulator = Chip8::blank();
+        emulator.run_opcode(0x603E);
+        emulator.run_opcode(0xD015);
+        assert!(emulator.drain_events().is_empty());
+    }
+
+    #[test]
+    fn blank_columns_past_the_edge() {
+        for clip_sprites in [true, false] {
+            let mut emulator = Chip8::blank();
+            emulator.set_quirks(Quirks {
+                clip_sprites,
+                ..Quirks::default()
+            });
+            emulator.set_report_edge_draws(true);
+            emulator.run_opcode(0xF029); // I = font glyph 0, 4 pixels wide
+            emulator.run_opcode(0x603C); // V0 = 60
+            emulator.run_opcode(0xD015); // only its unlit columns cross the edge
+            assert!(emulator.drain_events().is_empty());
+            assert_eq!(emulator.framebuffer()[0][60..64], [1; 4]);
+        }
+    }
+
+    #[test]
+    fn wrapped_pixels_collide() {
+        let draw_across_edge = |clip_sprites| {
+            let mut emulator = Chip8::blank();
+            emulator.set_quirks(Quirks {
+                clip_sprites,
+                ..Quirks::default()
+            });
+            emulator.ram[0x300] = 0xC0; // x = 0 and 1
+            emulator.ram[0x301] = 0xF0; // 4 pixels wide
+            emulator.run_opcode(0xA300);
+            emulator.run_opcode(0xD111); // at (0, 0)
+            emulator.run_opcode(0xA301);
+            emulator.run_opcode(0x603E); // V0 = 62
+            emulator.run_opcode(0xD011); // at (62, 0), wraps to x = 0 and 1
+            emulator
+        };
+
+        let wrapped = draw_across_edge(false);
+        assert_eq!(wrapped.v[0xF], 1);
+        assert_eq!(wrapped.last_collision_count(), 2);
+        assert_eq!(wrapped.framebuffer()[0][..2], [0, 0]);
+        assert_eq!(wrapped.framebuffer()[0][62..64], [1, 1]);
+
+        let clipped = draw_across_edge(true);
+        assert_eq!(clipped.v[0xF], 0);
+        assert_eq!(clipped.framebuffer()[0][..2], [1, 1]);
+    }
+
+    #[test]
+    fn no_display_activity() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x7001, // V0 += 1
+            0x1200, // loop
+        ]);
+        emulator.set_no_display_activity_threshold(100);
+        for _ in 0..99 {
+            emulator.step();
+        }
+        assert!(emulator.drain_events().is_empty());
+
+        emulator.step();
+        let events = emulator.drain_events();
+        assert_eq!(events, [Chip8Event::NoDisplayActivity { cycles: 100 }]);
+
+        // reported only once
+        for _ in 0..500 {
+            emulator.step();
+        }
+        assert!(emulator.drain_events().is_empty());
+    }
+
+    #[test]
+    fn display_activity_resets_the_count() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x7001, // V0 += 1
+            0x3010, // skip unless V0 == 0x10
+            0x00E0, // clear
+            0x1200, // loop
+        ]);
+        emulator.set_no_display_activity_threshold(20);
+        for _ in 0..1000 {
+            emulator.step();
+        }
+        assert!(emulator.drain_events().is_empty());
+    }
+
+    #[test]
+    fn big_sprite_in_high_res() {
+        let quirks = Quirks {
+            lores_wide_sprites: false,
+            ..Quirks::default()
+        };
+        let diagonal: Vec<_> = (0..16).map(|i| (i, i)).collect();
+        assert_eq!(lit_pixels(&draw_big_sprite(true, quirks)), diagonal);
+    }
+
+    #[test]
+    fn big_sprite_in_low_res() {
+        // by default DXY0 draws 16x16 in low-res too
+        let diagonal: Vec<_> = (0..16).map(|i| (i, i)).collect();
+        let screen = draw_big_sprite(false, Quirks::default());
+        assert_eq!(lit_pixels(&screen), diagonal);
+
+        // otherwise it's 8 pixels wide, one byte per row
+        let quirks = Quirks {
+            lores_wide_sprites: false,
+            ..Quirks::default()
+        };
+        let screen = draw_big_sprite(false, quirks);
+        let every_other_row: Vec<_> = (0..8).map(|i| (i, 2 * i)).collect();
+        assert_eq!(lit_pixels(&screen), every_other_row);
+    }
+
+    #[test]
+    fn execution_signature() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x08, // V0 = 8
+            0x61, 0x00, // V1 = 0
+            0xA2, 0x14, // I = sprite
+            0xD1, 0x10, // big sprite at (0, 0)
+            0xA2, 0x15, // I = sprite + 1
+            0xD0, 0x11, // one row at (8, 0), collides only with a 16 pixel wide sprite
+            0x3F, 0x01, // skip if VF == 1
+            0x62, 0x01,
+            0x12, 0x10, // loop
+            0x00, 0x00,
+            0x00, 0x80, // sprite, the rest of it is blank RAM
+        ];
+        let signature = |quirks: Quirks| {
+            let mut emulator = Chip8::from_bytes(&rom);
+            emulator.set_quirks(quirks);
+            for _ in 0..20 {
+                emulator.start_cycle();
+            }
+            emulator.execution_signature()
+        };
+
+        let wide = Quirks {
+            lores_wide_sprites: true,
+            ..Quirks::default()
+        };
+        let narrow = Quirks {
+            lores_wide_sprites: false,
+            ..Quirks::default()
+        };
+        assert_eq!(signature(wide), signature(wide));
+        assert_ne!(signature(wide), signature(narrow));
+
+        let mut emulator = Chip8::from_bytes(&rom);
+        let fresh = emulator.execution_signature();
+        emulator.start_cycle();
+        assert_ne!(emulator.execution_signature(), fresh);
+        emulator.reset();
+        assert_eq!(emulator.execution_signature(), fresh);
+    }
+}
+
+/// VF behaviour of every 8XY_ instruction, following the flags test from
+/// https://github.com/Timendus/chip8-test-suite
+#[cfg(test)]
+mod vf_test {
+    use super::*;
+
+    const VF_SENTINEL: u8 = 0x55;
+
+    /// `run_with` the default quirks
+    fn run(op: u16, vx: u8, vy: u8) -> (u8, u8) {
+        run_with(Quirks::default(), op, vx, vy)
+    }
+
+    /// Runs `8XY{op}` with X = 1 and Y = 2, returns (VX, VF)
+    fn run_with(quirks: Quirks, op: u16, vx: u8, vy: u8) -> (u8, u8) {
+        let mut emulator = Chip8::blank();
+        emulator.set_quirks(quirks);
+        emulator.v[1] = vx;
+        emulator.v[2] = vy;
+        emulator.v[0xF] = VF_SENTINEL;
+        emulator.run_opcode(0x8120 | op);
+        (emulator.v[1], emulator.v[0xF])
+    }
+
+    /// Runs `8FY{op}` with Y = 2, the result and the flag both target VF
+    fn run_on_vf(quirks: Quirks, op: u16, vf: u8, vy: u8) -> u8 {
+        let mut emulator = Chip8::blank();
+        emulator.set_quirks(quirks);
+        emulator.v[0xF] = vf;
+        emulator.v[2] = vy;
+        emulator.run_opcode(0x8F20 | op);
+        emulator.v[0xF]
+    }
+
+    #[test]
+    fn set_leaves_vf() {
+        assert_eq!(run(0x0, 0x00, 0xFF), (0xFF, VF_SENTINEL));
+        assert_eq!(run(0x0, 0xFF, 0x00), (0x00, VF_SENTINEL));
+    }
+
+    #[test]
+    fn logic_leaves_vf() {
+        let keep = Quirks {
+            reset_vf_on_logic: false,
+            ..Quirks::default()
+        };
+        assert_eq!(run_with(keep, 0x1, 0xF0, 0x0F), (0xFF, VF_SENTINEL));
+        assert_eq!(run_with(keep, 0x1, 0x00, 0x00), (0x00, VF_SENTINEL));
+        assert_eq!(run_with(keep, 0x2, 0xF0, 0x3C), (0x30, VF_SENTINEL));
+        assert_eq!(run_with(keep, 0x2, 0xFF, 0x00), (0x00, VF_SENTINEL));
+        assert_eq!(run_with(keep, 0x3, 0xFF, 0xFF), (0x00, VF_SENTINEL));
+        assert_eq!(run_with(keep, 0x3, 0xF0, 0x0F), (0xFF, VF_SENTINEL));
+    }
+
+    #[test]
+    fn logic_resets_vf() {
+        assert_eq!(run(0x1, 0xF0, 0x0F), (0xFF, 0));
+        assert_eq!(run(0x2, 0xF0, 0x3C), (0x30, 0));
+        assert_eq!(run(0x3, 0xF0, 0x0F), (0xFF, 0));
+        // the reset comes after the result when VF is the destination
+        assert_eq!(run_on_vf(Quirks::default(), 0x1, 0xF0, 0x0F), 0);
+    }
+
+    #[test]
+    fn add_carry() {
+        assert_eq!(run(0x4, 0x00, 0x00), (0x00, 0));
+        assert_eq!(run(0x4, 0x01, 0xFE), (0xFF, 0));
+        assert_eq!(run(0x4, 0xFF, 0x01), (0x00, 1));
+        assert_eq!(run(0x4, 0xFF, 0xFF), (0xFE, 1));
+    }
+
+    #[test]
+    fn sub_borrow() {
+        // VF = 1 when there is no borrow, equal operands don't borrow
+        assert_eq!(run(0x5, 0x00, 0x00), (0x00, 1));
+        assert_eq!(run(0x5, 0x01, 0x00), (0x01, 1));
+        assert_eq!(run(0x5, 0x00, 0x01), (0xFF, 0));
+        assert_eq!(run(0x5, 0xFF, 0xFF), (0x00, 1));
+        assert_eq!(run(0x5, 0x01, 0xFF), (0x02, 0));
+    }
+
+    #[test]
+    fn reverse_sub_borrow() {
+        assert_eq!(run(0x7, 0x00, 0x00), (0x00, 1));
+        assert_eq!(run(0x7, 0x00, 0x01), (0x01, 1));
+        assert_eq!(run(0x7, 0x01, 0x00), (0xFF, 0));
+        assert_eq!(run(0x7, 0xFF, 0xFF), (0x00, 1));
+        assert_eq!(run(0x7, 0xFF, 0x01), (0x02, 0));
+    }
+
+    #[test]
+    fn shift_right_bit() {
+        assert_eq!(run(0x6, 0x00, 0x00), (0x00, 0));
+        assert_eq!(run(0x6, 0x00, 0x01), (0x00, 1));
+        assert_eq!(run(0x6, 0x00, 0x02), (0x01, 0));
+        assert_eq!(run(0x6, 0x00, 0xFF), (0x7F, 1));
+    }
+
+    #[test]
+    fn shift_left_bit() {
+        assert_eq!(run(0xE, 0x00, 0x00), (0x00, 0));
+        assert_eq!(run(0xE, 0x00, 0x01), (0x02, 0));
+        assert_eq!(run(0xE, 0x00, 0x80), (0x00, 1));
+        assert_eq!(run(0xE, 0x00, 0xFF), (0xFE, 1));
+    }
+
+    #[test]
+    fn flag_wins_over_result_in_vf() {
+        let vip = Quirks::default();
+        assert_eq!(run_on_vf(vip, 0x4, 0xFF, 0x01), 1);
+        assert_eq!(run_on_vf(vip, 0x4, 0x01, 0x01), 0);
+        assert_eq!(run_on_vf(vip, 0x5, 0x00, 0x01), 0);
+        assert_eq!(run_on_vf(vip, 0x5, 0x02, 0x01), 1);
+        assert_eq!(run_on_vf(vip, 0x7, 0x01, 0x00), 0);
+        assert_eq!(run_on_vf(vip, 0x7, 0x00, 0x01), 1);
+        assert_eq!(run_on_vf(vip, 0x6, 0x00, 0x03), 1);
+        assert_eq!(run_on_vf(vip, 0x6, 0x00, 0x02), 0);
+        assert_eq!(run_on_vf(vip, 0xE, 0x00, 0x80), 1);
+        assert_eq!(run_on_vf(vip, 0xE, 0x00, 0x7F), 0);
+    }
+
+    #[test]
+    fn shift_into_vf_keeps_the_shifted_out_bit() {
+        // the shifted value would be 0x01 or 0xFE, the flag is what's left
+        let vip = Quirks::default();
+        assert_eq!(run_on_vf(vip, 0x6, 0x00, 0x03), 1);
+        assert_eq!(run_on_vf(vip, 0x6, 0xFF, 0x02), 0);
+        assert_eq!(run_on_vf(vip, 0xE, 0x00, 0xFF), 1);
+        assert_eq!(run_on_vf(vip, 0xE, 0xFF, 0x7F), 0);
+
+        // in place VF itself is shifted, VY doesn't matter
+        let in_place = Quirks {
+            shift_uses_vy: false,
+            ..vip
+        };
+        assert_eq!(run_on_vf(in_place, 0x6, 0x03, 0x00), 1);
+        assert_eq!(run_on_vf(in_place, 0x6, 0x02, 0xFF), 0);
+        assert_eq!(run_on_vf(in_place, 0xE, 0xFF, 0x00), 1);
+        assert_eq!(run_on_vf(in_place, 0xE, 0x7F, 0xFF), 0);
+    }
+
+    #[test]
+    fn shift_in_place_ignores_vy() {
+        for (shift_uses_vy, op, expected) in [
+            (false, 0x6, (0x40, 1)),
+            (false, 0xE, (0x02, 1)),
+            (true, 0x6, (0x7F, 0)),
+            (true, 0xE, (0xFC, 1)),
+        ] {
+            let mut emulator = Chip8::blank();
+            emulator.quirks.shift_uses_vy = shift_uses_vy;
+            emulator.v[1] = 0x81;
+            emulator.v[2] = 0xFE;
+            emulator.run_opcode(0x8120 | op);
+            assert_eq!((emulator.v[1], emulator.v[0xF]), expected);
+            assert_eq!(emulator.v[2], 0xFE);
+        }
+    }
 }
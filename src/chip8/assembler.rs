@@ -0,0 +1,327 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{encode, Instruction};
+use crate::Chip8Error;
+
+/// Programs are assembled to run where ROMs are loaded.
+const ORIGIN: usize = 0x200;
+
+/// An operand as written, numbers and labels are resolved once every label
+/// is known.
+#[derive(Clone, Copy)]
+enum Operand<'a> {
+    Register(u8),
+    Index,       // I
+    IndexMemory, // [I]
+    Delay,       // DT
+    Sound,       // ST
+    Key,         // K
+    Font,        // F
+    Bcd,         // B
+    Flags,       // R
+    Value(&'a str),
+}
+
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<Operand<'a>>,
+}
+
+/// Assembles a program in the syntax `disassemble` prints, to be loaded at
+/// 0x200.
+///
+/// Every line holds an instruction like `LD I, sprite`, a directive or
+/// nothing, optionally after a `label:` and before a `; comment`. Numbers
+/// are decimal, `0x` hex or `0b` binary, and a label can be used wherever an
+/// address or a number is expected.
+///
+/// `DB 0x12, 0x34` emits raw bytes and `DW 0x1234` big-endian words, so data
+/// like sprites can sit between the code:
+///
+/// ```text
+///         LD I, sprite
+///         DRW V0, V0, 2
+/// sprite: DB 0b11000011, 0x3C
+/// ```
+pub fn assemble(source: &str) -> Result<Vec<u8>, Chip8Error> {
+    let mut labels = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut address = ORIGIN;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let error = |reason| Chip8Error::InvalidAssembly { line, reason };
+
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+        while let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(error("invalid label"));
+            }
+            if labels.insert(label, address).is_some() {
+                return Err(error("duplicate label"));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands: Vec<_> = match operands.trim() {
+            "" => Vec::new(),
+            operands => operands.split(',').map(|op| operand(op.trim())).collect(),
+        };
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        address += match mnemonic.as_str() {
+            "DB" => operands.len(),
+            "DW" => operands.len() * 2,
+            _ => 2,
+        };
+        statements.push(Statement {
+            line,
+            mnemonic,
+            operands,
+        });
+    }
+
+    let mut rom = Vec::new();
+    for statement in &statements {
+        let error = |reason| Chip8Error::InvalidAssembly {
+            line: statement.line,
+            reason,
+        };
+        let operands = statement.operands.as_slice();
+        match statement.mnemonic.as_str() {
+            "DB" | "DW" if operands.is_empty() => return Err(error("missing data")),
+            "DB" => {
+                for &operand in operands {
+                    let byte = value(operand, &labels, 0xFF).map_err(error)?;
+                    rom.push(byte as u8);
+                }
+            }
+            "DW" => {
+                for &operand in operands {
+                    let word = value(operand, &labels, 0xFFFF).map_err(error)?;
+                    rom.extend_from_slice(&(word as u16).to_be_bytes());
+                }
+            }
+            mnemonic => {
+                let instruction = instruction(mnemonic, operands, &labels).map_err(error)?;
+                rom.extend_from_slice(&encode(instruction).to_be_bytes());
+            }
+        }
+    }
+    Ok(rom)
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn operand(text: &str) -> Operand<'_> {
+    let register = text
+        .strip_prefix(['V', 'v'])
+        .filter(|digit| digit.len() == 1)
+        .and_then(|digit| u8::from_str_radix(digit, 16).ok());
+    if let Some(register) = register {
+        return Operand::Register(register);
+    }
+    match text.to_ascii_uppercase().as_str() {
+        "I" => Operand::Index,
+        "[I]" => Operand::IndexMemory,
+        "DT" => Operand::Delay,
+        "ST" => Operand::Sound,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "B" => Operand::Bcd,
+        "R" => Operand::Flags,
+        _ => Operand::Value(text),
+    }
+}
+
+/// Resolves a number or label operand that has to fit in `max`.
+fn value(
+    operand: Operand,
+    labels: &BTreeMap<&str, usize>,
+    max: usize,
+) -> Result<usize, &'static str> {
+    let Operand::Value(text) = operand else {
+        return Err("expected a number or a label");
+    };
+    let value = if is_label(text) {
+        *labels.get(text).ok_or("unknown label")?
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        usize::from_str_radix(hex, 16).map_err(|_| "invalid number")?
+    } else if let Some(binary) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        usize::from_str_radix(binary, 2).map_err(|_| "invalid number")?
+    } else {
+        text.parse().map_err(|_| "invalid number")?
+    };
+    if value > max {
+        return Err("value out of range");
+    }
+    Ok(value)
+}
+
+fn instruction(
+    mnemonic: &str,
+    operands: &[Operand],
+    labels: &BTreeMap<&str, usize>,
+) -> Result<Instruction, &'static str> {
+    use Operand::*;
+    let address = |operand| value(operand, labels, 0xFFF);
+    let byte = |operand| value(operand, labels, 0xFF).map(|value| value as u8);
+    let nibble = |operand| value(operand, labels, 0xF).map(|value| value as u8);
+
+    let instruction = match (mnemonic, operands) {
+        ("NOP", []) => Instruction::Nop,
+        ("CLS", []) => Instruction::Clear,
+        ("RET", []) => Instruction::Return,
+        ("JP", [Register(0), target]) => {
+            let target = address(*target)?;
+            Instruction::JumpByRegister((target >> 8) as u8, target)
+        }
+        ("JP", [target]) => Instruction::Jump(address(*target)?),
+        ("CALL", [target]) => Instruction::SubRoutine(address(*target)?),
+        ("SE", [Register(x), Register(y)]) => Instruction::SkipOnXeqY(*x, *y),
+        ("SE", [Register(x), value]) => Instruction::SkipOnXeqV(*x, byte(*value)?),
+        ("SNE", [Register(x), Register(y)]) => Instruction::SkipOnXneqY(*x, *y),
+        ("SNE", [Register(x), value]) => Instruction::SkipOnXneqV(*x, byte(*value)?),
+        ("LD", [Register(x), Register(y)]) => Instruction::SetXtoY(*x, *y),
+        ("LD", [Register(x), Delay]) => Instruction::StoreDeelayInRegister(*x),
+        ("LD", [Register(x), Key]) => Instruction::WaitUserInput(*x),
+        ("LD", [Register(x), IndexMemory]) => Instruction::FillRegisters(*x),
+        ("LD", [Register(x), Flags]) => Instruction::LoadFlags(*x),
+        ("LD", [Register(x), value]) => Instruction::LoadNormalRegister(*x, byte(*value)?),
+        ("LD", [Index, target]) => Instruction::LoadIndexRegister(address(*target)? as u16),
+        ("LD", [Delay, Register(x)]) => Instruction::SetDeelayFromRegister(*x),
+        ("LD", [Sound, Register(x)]) => Instruction::SetSoundTimerFromRegister(*x),
+        ("LD", [Font, Register(x)]) => Instruction::LoadFont(*x),
+        ("LD", [Bcd, Register(x)]) => Instruction::StoreRegisterInBCD(*x),
+        ("LD", [IndexMemory, Register(x)]) => Instruction::StoreRegistersInMemmory(*x),
+        ("LD", [Flags, Register(x)]) => Instruction::StoreFlags(*x),
+        ("ADD", [Register(x), Register(y)]) => Instruction::AddYtoX(*x, *y),
+        ("ADD", [Register(x), value]) => Instruction::AddToNormalRegister(*x, byte(*value)?),
+        ("ADD", [Index, Register(x)]) => Instruction::AddRegisterToIndex(*x),
+        ("OR", [Register(x), Register(y)]) => Instruction::SetXtoXorY(*x, *y),
+        ("AND", [Register(x), Register(y)]) => Instruction::SetXtoXandY(*x, *y),
+        ("XOR", [Register(x), Register(y)]) => Instruction::SetXtoXxorY(*x, *y),
+        ("SUB", [Register(x), Register(y)]) => Instruction::SubYfromX(*x, *y),
+        ("SUBN", [Register(x), Register(y)]) => Instruction::SetXtoYMinusX(*x, *y),
+        ("SHR", [Register(x)]) => Instruction::SetXtoYshiftRightOnce(*x, *x),
+        ("SHR", [Register(x), Register(y)]) => Instruction::SetXtoYshiftRightOnce(*x, *y),
+        ("SHL", [Register(x)]) => Instruction::SetXtoYshiftLeftOnce(*x, *x),
+        ("SHL", [Register(x), Register(y)]) => Instruction::SetXtoYshiftLeftOnce(*x, *y),
+        ("RND", [Register(x), value]) => Instruction::LoadRegisterWithRandom(*x, byte(*value)?),
+        ("DRW", [Register(x), Register(y), rows]) => {
+            Instruction::DrawSprite(*x, *y, nibble(*rows)?)
+        }
+        ("SKP", [Register(x)]) => Instruction::SkipIfPressed(*x),
+        ("SKNP", [Register(x)]) => Instruction::SkipNotPressed(*x),
+        ("AUDIO", []) => Instruction::LoadAudioPattern,
+        ("PITCH", [Register(x)]) => Instruction::SetPitchFromRegister(*x),
+        ("PLANE", [planes]) => Instruction::SelectPlanes(nibble(*planes)?),
+        ("LOW", []) => Instruction::LowResolution,
+        ("HIGH", []) => Instruction::HighResolution,
+        ("SCD", [rows]) => Instruction::ScrollDown(nibble(*rows)?),
+        ("SCU", [rows]) => Instruction::ScrollUp(nibble(*rows)?),
+        ("SCR", []) => Instruction::ScrollRight,
+        ("SCL", []) => Instruction::ScrollLeft,
+        _ => return Err("unknown instruction or operands"),
+    };
+    Ok(instruction)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chip8::decode;
+    use crate::{disassemble, Chip8};
+
+    #[test]
+    fn sprite_data_between_code() {
+        let source = "
+            ; draws a sprite defined with DB
+                    LD V0, 8
+                    LD I, sprite
+                    DRW V0, V0, 2
+            done:   JP done
+            sprite: DB 0b11000011, 0x3C
+                    DW table
+            table:
+        ";
+        let rom = assemble(source).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            0x60, 0x08,
+            0xA2, 0x08,
+            0xD0, 0x02,
+            0x12, 0x06,
+            0xC3, 0x3C,
+            0x02, 0x0C,
+        ];
+        assert_eq!(rom, expected);
+
+        let listing = disassemble(&rom, 0x200);
+        assert!(listing.contains("0202  A208  LD I, 0x208"));
+        assert!(listing.contains("0204  D002  DRW V0, V0, 2"));
+
+        let mut emulator = Chip8::from_bytes(&rom);
+        for _ in 0..4 {
+            emulator.step();
+        }
+        let row = |y: usize| &emulator.screen[y][8..16];
+        assert_eq!(row(8), [1, 1, 0, 0, 0, 0, 1, 1]);
+        assert_eq!(row(9), [0, 0, 1, 1, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn round_trips_every_instruction() {
+        for opcode in 0..=u16::MAX {
+            let Some(instruction) = decode(opcode) else {
+                continue;
+            };
+            let rom = assemble(&instruction.to_string()).unwrap();
+            assert_eq!(rom, opcode.to_be_bytes(), "{instruction}");
+        }
+    }
+
+    #[test]
+    fn assembly_errors() {
+        let error = |source| assemble(source).unwrap_err();
+        assert_eq!(
+            error("CLS\nJP nowhere"),
+            Chip8Error::InvalidAssembly {
+                line: 2,
+                reason: "unknown label"
+            }
+        );
+        assert_eq!(
+            error("DB 0x100"),
+            Chip8Error::InvalidAssembly {
+                line: 1,
+                reason: "value out of range"
+            }
+        );
+        assert_eq!(
+            error("a:\na: CLS"),
+            Chip8Error::InvalidAssembly {
+                line: 2,
+                reason: "duplicate label"
+            }
+        );
+        assert_eq!(
+            error("LD K, V0"),
+            Chip8Error::InvalidAssembly {
+                line: 1,
+                reason: "unknown instruction or operands"
+            }
+        );
+    }
+}
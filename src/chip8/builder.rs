@@ -0,0 +1,197 @@
+use alloc::vec::Vec;
+
+use super::{Chip8, MemoryLayout};
+use crate::{Chip8Error, Quirks};
+
+/// Options for a new machine, see `Chip8::builder`. Everything set here
+/// survives a `reset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chip8Builder {
+    layout: MemoryLayout,
+    quirks: Quirks,
+    cycles_per_frame: usize,
+    clock_hz: Option<u32>,
+    interpreter: Option<Vec<u8>>,
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Self {
+        Chip8Builder {
+            layout: MemoryLayout::default(),
+            quirks: Quirks::default(),
+            cycles_per_frame: 10,
+            clock_hz: None,
+            interpreter: None,
+        }
+    }
+}
+
+impl Chip8Builder {
+    /// Puts the standard font at 0x50 (the default). ROMs that bring their
+    /// own font can turn this off, FX29 then points at whatever they put there.
+    pub fn load_builtin_font(mut self, load: bool) -> Self {
+        self.layout.builtin_font = load;
+        self
+    }
+
+    /// Where the ROM is loaded and execution starts, 0x200 by default. ETI 660
+    /// programs expect 0x600.
+    pub fn load_address(mut self, address: usize) -> Self {
+        self.layout.load_address = address;
+        self
+    }
+
+    /// Fills 0x000..0x200 with `image` instead of zeros, for the few VIP
+    /// ROMs that read bytes of the interpreter that used to live there.
+    /// Shorter images are padded with zeros. The font still goes on top at
+    /// 0x50 unless `load_builtin_font(false)`.
+    pub fn interpreter_image(mut self, image: &[u8]) -> Self {
+        self.interpreter = Some(image.to_vec());
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Instructions `run_frame` executes per 60hz frame, 10 by default.
+    pub fn cycles_per_frame(mut self, cycles: usize) -> Self {
+        self.cycles_per_frame = cycles;
+        self.clock_hz = None;
+        self
+    }
+
+    /// Instructions executed per second instead of per frame, rounded to a
+    /// whole number for every 60hz frame. Overrides `cycles_per_frame`.
+    pub fn clock_hz(mut self, hz: u32) -> Self {
+        self.clock_hz = Some(hz);
+        self
+    }
+
+    /// Builds the machine with `rom` loaded, failing with `InvalidConfig` if
+    /// the clock is 0 or too slow to run an instruction every frame, or the
+    /// interpreter image is over 0x200 bytes.
+    pub fn build(mut self, rom: &[u8]) -> Result<Chip8, Chip8Error> {
+        let cycles_per_frame = match self.clock_hz {
+            Some(hz) => cycles_for_clock(hz)?,
+            None if self.cycles_per_frame == 0 => {
+                let reason = "less than one cycle per frame";
+                return Err(Chip8Error::InvalidConfig { reason });
+            }
+            None => self.cycles_per_frame,
+        };
+        if let Some(image) = &self.interpreter {
+            let mut interpreter = [0; 0x200];
+            let reason = "interpreter image is over 0x200 bytes";
+            let space = interpreter.get_mut(..image.len());
+            let space = space.ok_or(Chip8Error::InvalidConfig { reason })?;
+            space.copy_from_slice(image);
+            self.layout.interpreter = Some(interpreter);
+        }
+
+        let mut machine = Chip8::with_layout(rom, self.layout)?;
+        machine.quirks = self.quirks;
+        machine.cycles_per_frame = cycles_per_frame;
+        Ok(machine)
+    }
+}
+
+/// Instructions per 60hz frame for a clock of `hz`, rounded.
+pub(super) fn cycles_for_clock(hz: u32) -> Result<usize, Chip8Error> {
+    let reason = match (hz as usize + 30) / 60 {
+        _ if hz == 0 => "clock_hz is 0",
+        0 => "less than one cycle per frame",
+        cycles => return Ok(cycles),
+    };
+    Err(Chip8Error::InvalidConfig { reason })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn without_builtin_font() {
+        let builder = Chip8::builder().load_builtin_font(false);
+        let mut emulator = builder.build(&[]).unwrap();
+        assert!(emulator.ram[0x50..0xA0].iter().all(|byte| *byte == 0));
+
+        // the ROM's own data is what FX29 finds
+        emulator.ram[0x50] = 0xAA;
+        emulator.run_opcode(0xF029);
+        assert_eq!(emulator.ram[emulator.i as usize], 0xAA);
+
+        emulator.reset();
+        assert_eq!(emulator.ram[0x50], 0);
+        assert_eq!(Chip8::builder().build(&[]).unwrap().ram[0x50], 0xF0);
+    }
+
+    #[test]
+    fn eti_660_load_address() {
+        let builder = Chip8::builder().load_address(0x600);
+        let mut emulator = builder.build(&[0x60, 0x05, 0x16, 0x00]).unwrap();
+        assert_eq!(emulator.pc, 0x600);
+        assert_eq!(emulator.ram[0x600..0x604], [0x60, 0x05, 0x16, 0x00]);
+        assert!(emulator.ram[0x200..0x600].iter().all(|byte| *byte == 0));
+
+        emulator.step();
+        emulator.reset();
+        assert_eq!(emulator.pc, 0x600);
+
+        let rom = [0; 4096 - 0x600 + 1];
+        let builder = Chip8::builder().load_address(0x600);
+        let err = builder.build(&rom).unwrap_err();
+        let max = 4096 - 0x600;
+        assert_eq!(err, Chip8Error::RomTooLarge { size: max + 1, max });
+    }
+
+    #[test]
+    fn invalid_clock() {
+        let err = Chip8::builder().clock_hz(0).build(&[]).unwrap_err();
+        let reason = "clock_hz is 0";
+        assert_eq!(err, Chip8Error::InvalidConfig { reason });
+        let err = Chip8::builder().cycles_per_frame(0).build(&[]).unwrap_err();
+        let reason = "less than one cycle per frame";
+        assert_eq!(err, Chip8Error::InvalidConfig { reason });
+        assert!(Chip8::builder().clock_hz(20).build(&[]).is_err());
+
+        let emulator = Chip8::builder().clock_hz(700).build(&[]).unwrap();
+        assert_eq!(emulator.cycles_per_frame(), 12);
+    }
+
+    #[test]
+    fn settings_survive_reset() {
+        let quirks = Quirks {
+            clip_sprites: false,
+            ..Quirks::default()
+        };
+        let mut emulator = Chip8::builder()
+            .quirks(quirks)
+            .cycles_per_frame(3)
+            .build(&[0x12, 0x00])
+            .unwrap();
+        emulator.reset();
+        assert_eq!(emulator.quirks(), quirks);
+        assert_eq!(emulator.cycles_per_frame(), 3);
+    }
+
+    #[test]
+    fn interpreter_image_under_the_font() {
+        let image: Vec<u8> = (0..0x100).map(|byte| byte as u8).collect();
+        let mut emulator = Chip8::builder()
+            .interpreter_image(&image)
+            .build(&[0x12, 0x00])
+            .unwrap();
+        emulator.reset();
+        assert_eq!(emulator.read_memory(0x00, 0x50), Ok(&image[..0x50]));
+        assert_eq!(emulator.read_memory(0x50, 2), Ok(&[0xF0, 0x90][..]));
+        assert_eq!(emulator.read_memory(0xA0, 0x60), Ok(&image[0xA0..]));
+        assert_eq!(emulator.read_memory(0x100, 1), Ok(&[0][..]));
+
+        let too_big = Chip8::builder().interpreter_image(&[0; 0x201]);
+        let reason = "interpreter image is over 0x200 bytes";
+        let err = too_big.build(&[]).unwrap_err();
+        assert_eq!(err, Chip8Error::InvalidConfig { reason });
+    }
+}
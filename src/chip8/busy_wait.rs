@@ -0,0 +1,85 @@
+use super::{decode, Chip8, Chip8Event, Instruction};
+
+/// Longest loop that counts as a busy wait, in bytes from its start to the
+/// jump back.
+const MAX_LOOP_LEN: usize = 16;
+
+impl Chip8 {
+    /// Reports a `Chip8Event::BusyWait` whenever the ROM starts spinning in a
+    /// small loop that only looks at the timers and the keypad, like a
+    /// FX07, 3XNN, 1NNN delay, so a frontend can take it easy until the
+    /// loop is left. Off by default.
+    pub fn set_report_busy_waits(&mut self, report: bool) {
+        self.report_busy_waits = report;
+        self.busy_wait = None;
+    }
+
+    pub(super) fn check_busy_wait(&mut self, pc: usize, instruction: Option<Instruction>) {
+        if let Some((start, end)) = self.busy_wait {
+            if !(start..=end).contains(&pc) {
+                self.busy_wait = None;
+            }
+        }
+        let Some(Instruction::Jump(start)) = instruction else {
+            return;
+        };
+        let is_small_loop = start <= pc && pc - start <= MAX_LOOP_LEN;
+        if !is_small_loop || self.busy_wait.is_some() || !self.only_polls(start, pc) {
+            return;
+        }
+        self.busy_wait = Some((start, pc));
+        let event = Chip8Event::BusyWait { start, end: pc };
+        self.events.push(event);
+    }
+
+    /// Whether the code from `start` up to the jump at `end` can't change
+    /// anything but the register FX07 loads into.
+    fn only_polls(&self, start: usize, end: usize) -> bool {
+        (start..end).step_by(2).all(|address| {
+            let opcode = u16::from_be_bytes([self.ram[address], self.ram[address + 1]]);
+            matches!(
+                decode(opcode),
+                Some(
+                    Instruction::Nop
+                        | Instruction::SkipOnXeqV(..)
+                        | Instruction::SkipOnXneqV(..)
+                        | Instruction::SkipOnXeqY(..)
+                        | Instruction::SkipOnXneqY(..)
+                        | Instruction::SkipIfPressed(_)
+                        | Instruction::SkipNotPressed(_)
+                        | Instruction::StoreDeelayInRegister(_)
+                )
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delay_timer_poll() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x6005, // V0 = 5
+            0xF015, // delay timer = V0
+            0xF107, // V1 = delay timer
+            0x3100, // skip if V1 == 0
+            0x1204, // back to the check
+            0x7201, // V2 += 1
+            0x120C, // halt, also a busy wait
+        ]);
+        emulator.set_report_busy_waits(true);
+        for _ in 0..6 {
+            emulator.run_frame().unwrap();
+        }
+        // reported once, however many times the loop ran
+        let (start, end) = (0x204, 0x208);
+        let delay = Chip8Event::BusyWait { start, end };
+        let (start, end) = (0x20C, 0x20C);
+        let halt = Chip8Event::BusyWait { start, end };
+        assert_eq!(emulator.drain_events(), [delay, halt]);
+        assert_eq!(emulator.v[2], 1);
+    }
+}
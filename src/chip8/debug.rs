@@ -0,0 +1,172 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Write;
+
+use super::Chip8;
+use crate::Chip8Error;
+
+impl Chip8 {
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// The index register I.
+    pub fn index(&self) -> u16 {
+        self.i
+    }
+
+    /// V0 to VF.
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    /// Return addresses of the subroutines currently being run, innermost last.
+    pub fn stack(&self) -> &[usize] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.deelay
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Bounds-checked view of `len` bytes of RAM starting at `addr`.
+    pub fn read_memory(&self, addr: usize, len: usize) -> Result<&[u8], Chip8Error> {
+        let range = self.ram_range(addr, len)?;
+        Ok(&self.ram[range])
+    }
+
+    /// Classic hexdump of RAM, 16 bytes per line with an ASCII gutter.
+    ///
+    /// Lines holding the byte PC or I point at are annotated, the dump stops
+    /// at the end of RAM.
+    ///
+    /// ```text
+    /// 0200  60 05 70 03 12 04                                 |`.p...|  <- PC 0x202
+    /// ```
+    pub fn memory_hex_view(&self, start: usize, len: usize) -> String {
+        let len = len.min(self.ram.len().saturating_sub(start));
+        let bytes = self.read_memory(start, len).unwrap_or(&[]);
+
+        let mut view = String::new();
+        for (line, chunk) in bytes.chunks(16).enumerate() {
+            let offset = start + line * 16;
+            let _ = write!(view, "{offset:04X} ");
+            for byte in chunk {
+                let _ = write!(view, " {byte:02X}");
+            }
+            for _ in chunk.len()..16 {
+                view.push_str("   ");
+            }
+
+            view.push_str("  |");
+            for byte in chunk {
+                let printable = (0x20..0x7F).contains(byte);
+                view.push(if printable { *byte as char } else { '.' });
+            }
+            view.push('|');
+
+            let line_range = offset..offset + chunk.len();
+            let mut marks = [("PC", self.pc), ("I", self.i as usize)]
+                .into_iter()
+                .filter(|(_, address)| line_range.contains(address));
+            if let Some((name, address)) = marks.next() {
+                let _ = write!(view, "  <- {name} {address:#05X}");
+                for (name, address) in marks {
+                    let _ = write!(view, ", {name} {address:#05X}");
+                }
+            }
+            view.push('\n');
+        }
+        view
+    }
+
+    /// The registers, timers and stack as one line of JSON, for tools outside
+    /// Rust. Unlike `save_state` the keys are meant to stay put:
+    ///
+    /// ```text
+    /// {"v":[5,0,...,0],"i":512,"pc":514,"delay_timer":0,"sound_timer":0,"stack":[],"cycles":1}
+    /// ```
+    ///
+    /// Every value is a number, so it's written directly instead of pulling
+    /// in a JSON library.
+    pub fn state_json(&self) -> String {
+        let v = json_list(self.v.iter().map(|&value| value as usize));
+        let stack = json_list(self.stack().iter().copied());
+        format!(
+            "{{\"v\":[{v}],\"i\":{},\"pc\":{},\"delay_timer\":{},\"sound_timer\":{},\"stack\":[{stack}],\"cycles\":{}}}",
+            self.i, self.pc, self.deelay, self.sound_timer, self.cycles
+        )
+    }
+}
+
+/// The values as the inside of a JSON array.
+fn json_list(values: impl Iterator<Item = usize>) -> String {
+    let mut list = String::new();
+    for (index, value) in values.enumerate() {
+        let separator = if index == 0 { "" } else { "," };
+        let _ = write!(list, "{separator}{value}");
+    }
+    list
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_memory_bounds() {
+        let emulator = Chip8::from_bytes(&[0x12, 0x34]);
+        assert_eq!(emulator.read_memory(0x200, 2), Ok(&[0x12, 0x34][..]));
+        assert_eq!(
+            emulator.read_memory(0xFFF, 2),
+            Err(Chip8Error::MemoryOutOfBounds {
+                address: 0xFFF,
+                len: 2
+            })
+        );
+    }
+
+    #[test]
+    fn hex_view_of_font() {
+        let mut emulator = Chip8::blank();
+        emulator.set_index(0x55);
+        let view = emulator.memory_hex_view(0x50, 20);
+        let lines: Vec<&str> = view.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "0050  F0 90 90 90 F0 20 60 20 20 70 F0 10 F0 80 F0 F0  |..... `  p......|  <- I 0x055"
+        );
+        assert!(lines[1].starts_with("0060  10 F0 10 F0   "));
+        assert!(lines[1].ends_with("|....|"));
+    }
+
+    #[test]
+    fn hex_view_marks_pc() {
+        let emulator = Chip8::from_bytes(&[0x60, 0x05]);
+        let view = emulator.memory_hex_view(0x1F8, 16);
+        assert!(view.starts_with("01F8  00 00 00 00 00 00 00 00 60 05"));
+        assert!(view.trim_end().ends_with("<- PC 0x200"));
+    }
+
+    #[test]
+    fn state_as_json() {
+        let mut emulator = Chip8::from_opcodes(&[0x2204, 0x0000, 0x6A42]);
+        emulator.step();
+        emulator.step();
+        let json = emulator.state_json();
+        let timers = ["delay_timer", "sound_timer"];
+        for key in ["v", "i", "pc", "stack", "cycles"].iter().chain(&timers) {
+            assert!(json.contains(&format!("\"{key}\":")), "{key} missing");
+        }
+        assert!(json.contains("\"v\":[0,0,0,0,0,0,0,0,0,0,66,0,0,0,0,0]"));
+        assert!(json.contains("\"pc\":518"));
+        assert!(json.contains("\"stack\":[514]"));
+        assert!(json.starts_with('{') && json.ends_with("\"cycles\":2}"));
+    }
+}
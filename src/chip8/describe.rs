@@ -0,0 +1,99 @@
+use alloc::format;
+use alloc::string::String;
+
+use super::{decode, Instruction};
+
+/// The mnemonic of `opcode` and what it does, for tooltips and the like.
+///
+/// ```text
+/// DRW V1, V2, 5: draw a 5 row sprite from I at (V1, V2), VF is set on collision
+/// ```
+///
+/// Opcodes that aren't instructions are described as `"unknown"`.
+pub fn describe_opcode(opcode: u16) -> String {
+    match decode(opcode) {
+        Some(instruction) => format!("{instruction}: {}", description(instruction)),
+        None => String::from("unknown"),
+    }
+}
+
+fn description(instruction: Instruction) -> String {
+    use Instruction::*;
+    match instruction {
+        Nop => String::from("do nothing"),
+        Clear => String::from("clear the screen"),
+        Return => String::from("return from a subroutine"),
+        Jump(address) => format!("jump to {address:#05X}"),
+        SubRoutine(address) => format!("call the subroutine at {address:#05X}"),
+        SkipOnXeqV(x, value) => format!("skip the next instruction if V{x:X} == {value:#04X}"),
+        SkipOnXneqV(x, value) => format!("skip the next instruction if V{x:X} != {value:#04X}"),
+        SkipOnXeqY(x, y) => format!("skip the next instruction if V{x:X} == V{y:X}"),
+        SkipOnXneqY(x, y) => format!("skip the next instruction if V{x:X} != V{y:X}"),
+        LoadNormalRegister(x, value) => format!("set V{x:X} to {value:#04X}"),
+        AddToNormalRegister(x, value) => format!("add {value:#04X} to V{x:X}, VF is left alone"),
+        SetXtoY(x, y) => format!("set V{x:X} to V{y:X}"),
+        SetXtoXorY(x, y) => format!("set V{x:X} to V{x:X} | V{y:X}"),
+        SetXtoXandY(x, y) => format!("set V{x:X} to V{x:X} & V{y:X}"),
+        SetXtoXxorY(x, y) => format!("set V{x:X} to V{x:X} ^ V{y:X}"),
+        AddYtoX(x, y) => format!("add V{y:X} to V{x:X}, VF is the carry"),
+        SubYfromX(x, y) => format!("subtract V{y:X} from V{x:X}, VF is 1 unless it borrows"),
+        SetXtoYMinusX(x, y) => {
+            format!("set V{x:X} to V{y:X} - V{x:X}, VF is 1 unless it borrows")
+        }
+        SetXtoYshiftRightOnce(x, y) => format!(
+            "set V{x:X} to V{y:X} >> 1 (V{x:X} >> 1 without shift_uses_vy), \
+             VF is the bit shifted out"
+        ),
+        SetXtoYshiftLeftOnce(x, y) => format!(
+            "set V{x:X} to V{y:X} << 1 (V{x:X} << 1 without shift_uses_vy), \
+             VF is the bit shifted out"
+        ),
+        LoadIndexRegister(address) => format!("set I to {address:#05X}"),
+        JumpByRegister(x, address) => {
+            format!("jump to {address:#05X} + V0, or V{x:X} with jump_with_vx")
+        }
+        LoadRegisterWithRandom(x, mask) => format!("set V{x:X} to a random byte & {mask:#04X}"),
+        DrawSprite(x, y, 0) => {
+            format!("draw a 16x16 sprite from I at (V{x:X}, V{y:X}), VF is set on collision")
+        }
+        DrawSprite(x, y, rows) => {
+            format!("draw a {rows} row sprite from I at (V{x:X}, V{y:X}), VF is set on collision")
+        }
+        SkipIfPressed(x) => format!("skip the next instruction if the key in V{x:X} is down"),
+        SkipNotPressed(x) => format!("skip the next instruction if the key in V{x:X} is up"),
+        StoreDeelayInRegister(x) => format!("set V{x:X} to the delay timer"),
+        WaitUserInput(x) => format!("wait for a key to be pressed and released, put it in V{x:X}"),
+        SetDeelayFromRegister(x) => format!("set the delay timer to V{x:X}"),
+        SetSoundTimerFromRegister(x) => format!("set the sound timer to V{x:X}"),
+        AddRegisterToIndex(x) => format!("add V{x:X} to I"),
+        LoadFont(x) => format!("point I at the font glyph for the low nibble of V{x:X}"),
+        StoreRegisterInBCD(x) => format!("store the 3 decimal digits of V{x:X} from I"),
+        StoreRegistersInMemmory(x) => format!("store V0 to V{x:X} in memory from I"),
+        FillRegisters(x) => format!("load V0 to V{x:X} from memory at I"),
+        LoadAudioPattern => String::from("load the 16 byte audio pattern at I"),
+        SetPitchFromRegister(x) => format!("set the audio pitch to V{x:X}"),
+        SelectPlanes(planes) => format!("draw on the bit planes in mask {planes}"),
+        StoreFlags(x) => format!("save V0 to V{x:X} in the RPL flags"),
+        LoadFlags(x) => format!("load V0 to V{x:X} from the RPL flags"),
+        LowResolution => String::from("switch to the 64x32 low-res display"),
+        HighResolution => String::from("switch to the 128x64 high-res display"),
+        ScrollDown(rows) => format!("scroll the screen down {rows} rows"),
+        ScrollUp(rows) => format!("scroll the screen up {rows} rows"),
+        ScrollRight => String::from("scroll the screen right 4 pixels"),
+        ScrollLeft => String::from("scroll the screen left 4 pixels"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn describe_draw() {
+        let description = describe_opcode(0xD125);
+        assert!(description.starts_with("DRW V1, V2, 5: "));
+        assert!(description.contains("5 row sprite"));
+        assert!(description.contains("(V1, V2)"));
+        assert_eq!(describe_opcode(0xFFFF), "unknown");
+    }
+}
@@ -0,0 +1,210 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::Chip8;
+
+/// One difference found by `Chip8::diff`, `this` is the machine `diff` was
+/// called on and `other` the one it was compared with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateDiff {
+    /// V0 to VF.
+    Register {
+        index: u8,
+        this: u8,
+        other: u8,
+    },
+    Index {
+        this: u16,
+        other: u16,
+    },
+    Pc {
+        this: usize,
+        other: usize,
+    },
+    DelayTimer {
+        this: u8,
+        other: u8,
+    },
+    SoundTimer {
+        this: u8,
+        other: u8,
+    },
+    Memory {
+        address: usize,
+        this: u8,
+        other: u8,
+    },
+    /// Pixels are compared over the whole 128x64 buffer, whatever the resolution.
+    Pixel {
+        x: usize,
+        y: usize,
+        this: bool,
+        other: bool,
+    },
+}
+
+impl Chip8 {
+    /// Everything that differs between the two machines, in the order of the
+    /// `StateDiff` variants and by address or position within each.
+    ///
+    /// Handy to find where a run diverges from a reference: step both and
+    /// stop at the first cycle this isn't empty.
+    pub fn diff(&self, other: &Chip8) -> Vec<StateDiff> {
+        let mut diffs = Vec::new();
+        for (index, (&this, &other)) in self.v.iter().zip(&other.v).enumerate() {
+            if this != other {
+                let index = index as u8;
+                diffs.push(StateDiff::Register { index, this, other });
+            }
+        }
+        if self.i != other.i {
+            diffs.push(StateDiff::Index {
+                this: self.i,
+                other: other.i,
+            });
+        }
+        if self.pc != other.pc {
+            diffs.push(StateDiff::Pc {
+                this: self.pc,
+                other: other.pc,
+            });
+        }
+        if self.deelay != other.deelay {
+            diffs.push(StateDiff::DelayTimer {
+                this: self.deelay,
+                other: other.deelay,
+            });
+        }
+        if self.sound_timer != other.sound_timer {
+            diffs.push(StateDiff::SoundTimer {
+                this: self.sound_timer,
+                other: other.sound_timer,
+            });
+        }
+        for (address, (&this, &other)) in self.ram.iter().zip(&other.ram).enumerate() {
+            if this != other {
+                diffs.push(StateDiff::Memory {
+                    address,
+                    this,
+                    other,
+                });
+            }
+        }
+        for (y, (this_row, other_row)) in self.screen.iter().zip(&other.screen).enumerate() {
+            for (x, (&this, &other)) in this_row.iter().zip(other_row).enumerate() {
+                if this != other {
+                    diffs.push(StateDiff::Pixel {
+                        x,
+                        y,
+                        this: this != 0,
+                        other: other != 0,
+                    });
+                }
+            }
+        }
+        diffs
+    }
+}
+
+/// The `(x, y)` of every pixel that differs between two `Chip8::framebuffer`s,
+/// row by row. Pixels on different planes count as different.
+pub fn framebuffer_diff(a: &[[u8; 128]; 64], b: &[[u8; 128]; 64]) -> Vec<(usize, usize)> {
+    let rows = a.iter().zip(b).enumerate();
+    rows.flat_map(|(y, (row_a, row_b))| {
+        let pixels = row_a.iter().zip(row_b).enumerate();
+        pixels
+            .filter(|(_, (a, b))| a != b)
+            .map(move |(x, _)| (x, y))
+    })
+    .collect()
+}
+
+/// Draws the top left `width` x `height` of `b` over `a`, a line per row.
+/// Pixels both agree on are `#` or `.`, the ones that differ are `+` if they
+/// are lit in `b` and `-` if not.
+pub fn framebuffer_diff_overlay(
+    a: &[[u8; 128]; 64],
+    b: &[[u8; 128]; 64],
+    width: usize,
+    height: usize,
+) -> String {
+    let mut overlay = String::new();
+    for (row_a, row_b) in a.iter().zip(b).take(height) {
+        for (a, b) in row_a.iter().zip(row_b).take(width) {
+            overlay.push(match (a == b, *b != 0) {
+                (true, true) => '#',
+                (true, false) => '.',
+                (false, true) => '+',
+                (false, false) => '-',
+            });
+        }
+        overlay.push('\n');
+    }
+    overlay
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_single_register() {
+        let rom = [0x60, 0x05, 0x12, 0x02];
+        let reference = Chip8::from_bytes(&rom);
+        let mut emulator = Chip8::from_bytes(&rom);
+        assert!(emulator.diff(&reference).is_empty());
+
+        emulator.v[0xA] = 0x42;
+        assert_eq!(
+            emulator.diff(&reference),
+            [StateDiff::Register {
+                index: 0xA,
+                this: 0x42,
+                other: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_in_order() {
+        let mut emulator = Chip8::blank();
+        let reference = Chip8::blank();
+        emulator.i = 0x300;
+        emulator.ram[0x300] = 1;
+        emulator.screen[5][3] = 1;
+
+        let diffs = emulator.diff(&reference);
+        let expected = [
+            StateDiff::Index {
+                this: 0x300,
+                other: 0,
+            },
+            StateDiff::Memory {
+                address: 0x300,
+                this: 1,
+                other: 0,
+            },
+            StateDiff::Pixel {
+                x: 3,
+                y: 5,
+                this: true,
+                other: false,
+            },
+        ];
+        assert_eq!(diffs, expected);
+    }
+
+    #[test]
+    fn one_pixel_regressed() {
+        let mut golden = Chip8::blank();
+        golden.screen[0][0] = 1;
+        golden.screen[1][2] = 1;
+        let mut screen = golden.screen;
+        screen[1][2] = 0;
+
+        assert_eq!(framebuffer_diff(&golden.screen, &screen), [(2, 1)]);
+        let overlay = framebuffer_diff_overlay(&golden.screen, &screen, 4, 2);
+        assert_eq!(overlay, "#...\n..-.\n");
+        assert!(framebuffer_diff(&screen, &screen).is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use super::{decode, sprite_bit, Instruction};
+
+/// Disassembles `code` as if it was loaded at `origin`, one instruction per
+/// line with its address and opcode.
+///
+/// Words that aren't instructions, usually sprite data, are listed as `DW`
+/// and a trailing odd byte as `DB`.
+///
+/// ```text
+/// 0200  00E0  CLS
+/// 0202  A22A  LD I, 0x22A
+/// ```
+pub fn disassemble(code: &[u8], origin: usize) -> String {
+    listing(code, origin, &[], &[])
+}
+
+/// Disassembles a ROM loaded at 0x200 like `disassemble`, but gives every
+/// JP and CALL target inside the ROM a `label_N` name, numbered by address.
+///
+/// ```text
+/// label_0:
+/// 0200  7001  ADD V0, 0x01
+/// 0202  1200  JP label_0
+/// ```
+///
+/// A DRW that follows a `LD I, addr` pointing inside the ROM, with no jump,
+/// label or other change of I in between, gets the sprite drawn under it.
+///
+/// ```text
+/// 0202  D012  DRW V0, V1, 2
+///             ; .##..##.
+///             ; ##.##.##
+/// ```
+pub fn disassemble_labeled(rom: &[u8]) -> String {
+    let mut targets: Vec<usize> = words(rom)
+        .filter_map(|opcode| match decode(opcode) {
+            Some(Instruction::Jump(address) | Instruction::SubRoutine(address)) => Some(address),
+            _ => None,
+        })
+        .filter(|address| (0x200..0x200 + rom.len()).contains(address))
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    let sprites = sprites(rom, &targets);
+    listing(rom, 0x200, &targets, &sprites)
+}
+
+/// A sprite to show under the DRW at `address`.
+struct Sprite<'a> {
+    address: usize,
+    data: &'a [u8],
+    width: usize,
+}
+
+/// Finds the DRW instructions drawing a sprite from a known place in the ROM,
+/// `labels` break the tracking since I could be anything coming from there.
+fn sprites<'a>(rom: &'a [u8], labels: &[usize]) -> Vec<Sprite<'a>> {
+    let mut sprites = Vec::new();
+    let mut index = None;
+    for (word, opcode) in words(rom).enumerate() {
+        let address = 0x200 + word * 2;
+        if labels.binary_search(&address).is_ok() {
+            index = None;
+        }
+        match decode(opcode) {
+            Some(Instruction::LoadIndexRegister(i)) => index = Some(i as usize),
+            Some(Instruction::DrawSprite(_, _, rows)) => {
+                // DXY0 is taken as the SCHIP 16x16 sprite
+                let (len, width) = match rows {
+                    0 => (32, 16),
+                    rows => (rows as usize, 8),
+                };
+                let data = index
+                    .and_then(|i| i.checked_sub(0x200))
+                    .and_then(|start| rom.get(start..start + len));
+                if let Some(data) = data {
+                    sprites.push(Sprite {
+                        address,
+                        data,
+                        width,
+                    });
+                }
+            }
+            Some(
+                Instruction::AddRegisterToIndex(_)
+                | Instruction::LoadFont(_)
+                | Instruction::StoreRegistersInMemmory(_)
+                | Instruction::FillRegisters(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpByRegister(..)
+                | Instruction::SubRoutine(_)
+                | Instruction::Return,
+            ) => index = None,
+            _ => (),
+        }
+    }
+    sprites
+}
+
+/// The opcodes in `code`, ignoring a trailing odd byte.
+fn words(code: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    code.chunks_exact(2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Writes the listing, `labels` are sorted addresses named after their index
+/// and `sprites` are sorted by address too.
+fn listing(code: &[u8], origin: usize, labels: &[usize], sprites: &[Sprite]) -> String {
+    let mut sprites = sprites.iter().peekable();
+    let label = |address: usize| labels.binary_search(&address).ok();
+
+    let mut listing = String::new();
+    for (word, bytes) in code.chunks(2).enumerate() {
+        let address = origin + word * 2;
+        if let Some(index) = label(address) {
+            let _ = writeln!(listing, "label_{index}:");
+        }
+        let _ = match *bytes {
+            [high, low] => {
+                let opcode = u16::from_be_bytes([high, low]);
+                let _ = write!(listing, "{address:04X}  {opcode:04X}  ");
+                match decode(opcode) {
+                    Some(Instruction::Jump(target)) if label(target).is_some() => {
+                        writeln!(listing, "JP label_{}", label(target).unwrap())
+                    }
+                    Some(Instruction::SubRoutine(target)) if label(target).is_some() => {
+                        writeln!(listing, "CALL label_{}", label(target).unwrap())
+                    }
+                    Some(instruction) => writeln!(listing, "{instruction}"),
+                    None => writeln!(listing, "DW 0x{opcode:04X}"),
+                }
+            }
+            [byte] => writeln!(listing, "{address:04X}  {byte:02X}    DB 0x{byte:02X}"),
+            _ => unreachable!(),
+        };
+        if let Some(sprite) = sprites.next_if(|sprite| sprite.address == address) {
+            for row in sprite.data.chunks(sprite.width / 8) {
+                listing.push_str("            ; ");
+                for x in 0..sprite.width {
+                    let lit = sprite_bit(row[x / 8], x);
+                    listing.push(if lit { '#' } else { '.' });
+                }
+                listing.push('\n');
+            }
+        }
+    }
+    listing
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disassemble_program() {
+        #[rustfmt::skip]
+        let code = [
+            0x00, 0xE0,
+            0x61, 0x2A,
+            0xD0, 0x15,
+            0x12, 0x00,
+            0xFF, 0xFF,
+            0x3C,
+        ];
+        let listing = disassemble(&code, 0x200);
+        let lines: Vec<_> = listing.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "0200  00E0  CLS",
+                "0202  612A  LD V1, 0x2A",
+                "0204  D015  DRW V0, V1, 5",
+                "0206  1200  JP 0x200",
+                "0208  FFFF  DW 0xFFFF",
+                "020A  3C    DB 0x3C",
+            ]
+        );
+    }
+
+    #[test]
+    fn labeled_backward_jump() {
+        #[rustfmt::skip]
+        let rom = [
+            0x22, 0x08, // CALL 0x208
+            0x70, 0x01, // V0 += 1
+            0x30, 0x10, // skip if V0 == 0x10
+            0x12, 0x02, // jump back
+            0x00, 0xEE, // RET
+            0x13, 0x00, // outside the ROM, stays an address
+        ];
+        let listing = disassemble_labeled(&rom);
+        let lines: Vec<_> = listing.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "0200  2208  CALL label_1",
+                "label_0:",
+                "0202  7001  ADD V0, 0x01",
+                "0204  3010  SE V0, 0x10",
+                "0206  1202  JP label_0",
+                "label_1:",
+                "0208  00EE  RET",
+                "020A  1300  JP 0x300",
+            ]
+        );
+    }
+
+    #[test]
+    fn sprite_preview() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA2, 0x08, // I = the sprite
+            0x60, 0x05, // V0 = 5
+            0xD0, 0x02, // draw it
+            0x00, 0xEE, // RET
+            0x66, 0xDB, // the sprite
+        ];
+        let listing = disassemble_labeled(&rom);
+        let lines: Vec<_> = listing.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "0200  A208  LD I, 0x208",
+                "0202  6005  LD V0, 0x05",
+                "0204  D002  DRW V0, V0, 2",
+                "            ; .##..##.",
+                "            ; ##.##.##",
+                "0206  00EE  RET",
+                "0208  66DB  LD V6, 0xDB",
+            ]
+        );
+
+        // I isn't known after a jump
+        let rom = [0xA2, 0x06, 0x12, 0x04, 0xD0, 0x02, 0x66, 0xDB];
+        let lines = disassemble_labeled(&rom).lines().count();
+        assert_eq!(lines, 5);
+    }
+}
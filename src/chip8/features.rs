@@ -0,0 +1,227 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use super::decode;
+
+/// Extensions a ROM looks like it relies on, see `required_features`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FeatureSet {
+    /// High-res mode, scrolling, 16x16 sprites, the big font or the RPL flags.
+    pub super_chip: bool,
+    /// Bit planes, audio patterns, 00DN scrolling or register ranges.
+    pub xo_chip: bool,
+    /// F000 NNNN, which can point I past the first 4KB.
+    pub extended_memory: bool,
+}
+
+/// Guesses which extensions a ROM needs by looking for their opcodes.
+///
+/// Every 2 byte word of the ROM is checked as if it was code, so sprite data
+/// can make this report an extension the ROM doesn't actually use. It's good
+/// enough to pick a compatibility profile, not to prove anything.
+pub fn required_features(rom: &[u8]) -> FeatureSet {
+    let mut features = FeatureSet::default();
+    let mut words = rom
+        .chunks_exact(2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+
+    while let Some(opcode) = words.next() {
+        let low_byte = opcode & 0x00FF;
+        match opcode & 0xF000 {
+            0x0000 => match opcode & 0x0FFF {
+                0x00FB..=0x00FF => features.super_chip = true,
+                0x00C1..=0x00CF => features.super_chip = true,
+                0x00D1..=0x00DF => features.xo_chip = true,
+                _ => (),
+            },
+            0x5000 if matches!(opcode & 0x000F, 0x2 | 0x3) => features.xo_chip = true,
+            0xD000 if opcode & 0x000F == 0 => features.super_chip = true,
+            0xF000 if opcode == 0xF000 => {
+                // the address that follows isn't an instruction
+                words.next();
+                features.xo_chip = true;
+                features.extended_memory = true;
+            }
+            0xF000 => match low_byte {
+                0x30 | 0x75 | 0x85 => features.super_chip = true,
+                0x01 | 0x02 | 0x3A => features.xo_chip = true,
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+    features
+}
+
+/// Every opcode pattern of CHIP-8, SUPER-CHIP 1.1 and XO-CHIP, with an
+/// opcode that matches it.
+const SPEC_OPCODES: [(&str, u16); 51] = [
+    ("0NNN", 0x0123),
+    ("00CN", 0x00C1),
+    ("00DN", 0x00D1),
+    ("00E0", 0x00E0),
+    ("00EE", 0x00EE),
+    ("00FB", 0x00FB),
+    ("00FC", 0x00FC),
+    ("00FD", 0x00FD),
+    ("00FE", 0x00FE),
+    ("00FF", 0x00FF),
+    ("1NNN", 0x1234),
+    ("2NNN", 0x2234),
+    ("3XNN", 0x3122),
+    ("4XNN", 0x4122),
+    ("5XY0", 0x5120),
+    ("5XY2", 0x5122),
+    ("5XY3", 0x5123),
+    ("6XNN", 0x6122),
+    ("7XNN", 0x7122),
+    ("8XY0", 0x8120),
+    ("8XY1", 0x8121),
+    ("8XY2", 0x8122),
+    ("8XY3", 0x8123),
+    ("8XY4", 0x8124),
+    ("8XY5", 0x8125),
+    ("8XY6", 0x8126),
+    ("8XY7", 0x8127),
+    ("8XYE", 0x812E),
+    ("9XY0", 0x9120),
+    ("ANNN", 0xA234),
+    ("BNNN", 0xB234),
+    ("CXNN", 0xC122),
+    ("DXYN", 0xD125),
+    ("EX9E", 0xE19E),
+    ("EXA1", 0xE1A1),
+    ("F000", 0xF000),
+    ("FN01", 0xF101),
+    ("F002", 0xF002),
+    ("FX07", 0xF107),
+    ("FX0A", 0xF10A),
+    ("FX15", 0xF115),
+    ("FX18", 0xF118),
+    ("FX1E", 0xF11E),
+    ("FX29", 0xF129),
+    ("FX30", 0xF130),
+    ("FX33", 0xF133),
+    ("FX3A", 0xF13A),
+    ("FX55", 0xF155),
+    ("FX65", 0xF165),
+    ("FX75", 0xF175),
+    ("FX85", 0xF185),
+];
+
+/// The opcode patterns of CHIP-8, SUPER-CHIP and XO-CHIP this build runs.
+pub fn supported_opcodes() -> Vec<&'static str> {
+    spec_opcodes(true)
+}
+
+/// The opcode patterns of CHIP-8, SUPER-CHIP and XO-CHIP this build doesn't
+/// run yet, like SUPER-CHIP's 00FD (exit). A ROM using them stops with an
+/// unknown opcode.
+pub fn unsupported_spec_opcodes() -> Vec<&'static str> {
+    spec_opcodes(false)
+}
+
+fn spec_opcodes(supported: bool) -> Vec<&'static str> {
+    SPEC_OPCODES
+        .iter()
+        .filter(|(_, opcode)| decode(*opcode).is_some() == supported)
+        .map(|(pattern, _)| *pattern)
+        .collect()
+}
+
+/// Counts how often every opcode pattern like `"6XNN"` appears in the ROM,
+/// see `Instruction::pattern`.
+///
+/// Like `required_features` the whole ROM is counted as code, data
+/// included, words that aren't instructions are left out.
+#[cfg(feature = "std")]
+pub fn static_opcode_histogram(rom: &[u8]) -> HashMap<String, usize> {
+    let mut histogram = HashMap::new();
+    let words = rom
+        .chunks_exact(2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+    for instruction in words.filter_map(decode) {
+        let pattern = instruction.pattern().to_string();
+        *histogram.entry(pattern).or_insert(0) += 1;
+    }
+    histogram
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_chip8() {
+        let rom = [0x00, 0xE0, 0xA2, 0x2A, 0xD0, 0x15, 0x12, 0x00];
+        assert_eq!(required_features(&rom), FeatureSet::default());
+    }
+
+    #[test]
+    fn super_chip_rom() {
+        #[rustfmt::skip]
+        let rom = [
+            0x00, 0xFF, // high-res
+            0xD0, 0x10, // 16x16 sprite
+            0x00, 0xC4, // scroll down
+        ];
+        let features = required_features(&rom);
+        assert!(features.super_chip);
+        assert!(!features.xo_chip);
+        assert!(!features.extended_memory);
+    }
+
+    #[test]
+    fn xo_chip_rom() {
+        #[rustfmt::skip]
+        let rom = [
+            0xF2, 0x01, // select planes
+            0x00, 0xD2, // scroll up
+        ];
+        let features = required_features(&rom);
+        assert!(features.xo_chip);
+        assert!(!features.super_chip);
+        assert!(!features.extended_memory);
+
+        // 00FF after F000 is an address, not a request for high-res
+        let rom = [0xF0, 0x00, 0x00, 0xFF];
+        let features = required_features(&rom);
+        assert!(features.xo_chip && features.extended_memory);
+        assert!(!features.super_chip);
+    }
+
+    #[test]
+    fn spec_coverage() {
+        let unsupported = unsupported_spec_opcodes();
+        assert!(unsupported.contains(&"00FD"));
+        assert!(unsupported.contains(&"FX30"));
+        // SUPER-CHIP scrolling is implemented
+        for scroll in ["00CN", "00FB", "00FC"] {
+            assert!(!unsupported.contains(&scroll));
+            assert!(supported_opcodes().contains(&scroll));
+        }
+        let total = supported_opcodes().len() + unsupported.len();
+        assert_eq!(total, SPEC_OPCODES.len());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn opcode_histogram() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x05, // V0 = 5
+            0x61, 0x0A, // V1 = 10
+            0xD0, 0x15, // draw
+            0x70, 0x01, // V0 += 1
+            0x12, 0x04, // loop
+            0xFF, 0xFF, // not an instruction
+        ];
+        let histogram = static_opcode_histogram(&rom);
+        assert_eq!(histogram.len(), 4);
+        assert_eq!(histogram["6XNN"], 2);
+        assert_eq!(histogram["DXYN"], 1);
+        assert_eq!(histogram["7XNN"], 1);
+        assert_eq!(histogram["1NNN"], 1);
+    }
+}
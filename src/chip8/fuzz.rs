@@ -0,0 +1,137 @@
+//! Runs random and mutated ROMs looking for instructions that panic instead
+//! of faulting. A ROM that panics is shrunk to a small one that still does,
+//! which is printed so it can become a regular test.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use super::Chip8;
+use crate::config::quirk_mut;
+use crate::Quirks;
+
+/// Instructions every ROM gets to run.
+const CYCLES: usize = 2000;
+
+/// xorshift, so a failure shows up again on the next run.
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// Runs `rom` for `CYCLES` instructions and returns the machine, `Err` with
+/// the panic message if anything panicked. The quirks and the keys pressed
+/// come from `seed`, so a run can be repeated.
+fn run(rom: &[u8], seed: u32) -> Result<Chip8, String> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut random = Random(seed);
+        let mut quirks = Quirks::default();
+        for (name, _) in Quirks::describe() {
+            if let Some(quirk) = quirk_mut(&mut quirks, name) {
+                *quirk = random.next().is_multiple_of(2);
+            }
+        }
+        let mut emulator = Chip8::from_bytes(rom);
+        emulator.set_quirks(quirks);
+        for cycle in 0..CYCLES {
+            if cycle.is_multiple_of(7) {
+                emulator.set_key((random.next() % 16) as u8, random.next().is_multiple_of(2));
+            }
+            if emulator.step().is_none() {
+                break;
+            }
+        }
+        emulator
+    }));
+    result.map_err(|payload| match payload.downcast_ref::<&str>() {
+        Some(message) => String::from(*message),
+        None => {
+            let message = payload.downcast_ref::<String>();
+            message.cloned().unwrap_or_default()
+        }
+    })
+}
+
+/// A smaller ROM that still `fails`: the tail is cut off and then every
+/// instruction that isn't needed is turned into a NOP.
+fn shrink(rom: &[u8], fails: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    let mut rom = rom.to_vec();
+    while rom.len() >= 2 && fails(&rom[..rom.len() - 2]) {
+        rom.truncate(rom.len() - 2);
+    }
+    for start in (0..rom.len()).step_by(2) {
+        let end = (start + 2).min(rom.len());
+        let kept = rom[start..end].to_vec();
+        rom[start..end].fill(0);
+        if !fails(&rom) {
+            rom[start..end].copy_from_slice(&kept);
+        }
+    }
+    rom
+}
+
+fn hex(rom: &[u8]) -> String {
+    rom.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+/// Fails the test with a reproducer if `rom` panics, and checks that a
+/// fault is one `run_frame` would report.
+fn check(rom: &[u8], seed: u32) {
+    match run(rom, seed) {
+        Ok(mut emulator) => {
+            if let Some(fault) = emulator.fault().cloned() {
+                let reported = emulator.run_frame();
+                assert_eq!(reported, Err(fault), "ROM {}, seed {seed:#X}", hex(rom));
+            }
+        }
+        Err(message) => {
+            let smallest = shrink(rom, |rom| run(rom, seed).is_err());
+            let smallest = hex(&smallest);
+            panic!("{message}, reproduce with ROM {smallest} and seed {seed:#X}");
+        }
+    }
+}
+
+#[test]
+fn random_roms() {
+    let mut random = Random(0x1BAD_B002);
+    for _ in 0..300 {
+        // up to all of the memory a ROM can have
+        let len = random.next() as usize % 0xE00;
+        let rom: Vec<u8> = (0..len).map(|_| random.next() as u8).collect();
+        check(&rom, random.next());
+    }
+}
+
+#[test]
+fn mutated_roms() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/roms");
+    let mut random = Random(0xC0FF_EE11);
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let seed = std::fs::read(entry.unwrap().path()).unwrap();
+        check(&seed, random.next());
+        for _ in 0..20 {
+            let mut rom = seed.clone();
+            for _ in 0..8 {
+                let Some(byte) = rom.get_mut(random.next() as usize % seed.len().max(1)) else {
+                    break;
+                };
+                *byte = random.next() as u8;
+            }
+            check(&rom, random.next());
+        }
+    }
+}
+
+#[test]
+fn shrink_to_reproducer() {
+    // anything that still has 00EE at an even offset "fails"
+    let fails = |rom: &[u8]| rom.chunks(2).any(|word| word == [0x00, 0xEE]);
+    let rom = [0x60, 0x01, 0x00, 0xEE, 0xA2, 0x00, 0x12, 0x00];
+    assert_eq!(shrink(&rom, fails), [0x00, 0x00, 0x00, 0xEE]);
+    assert_eq!(hex(&[0x00, 0xEE]), "00EE");
+}
@@ -0,0 +1,77 @@
+use alloc::vec::Vec;
+
+use super::{decode, Instruction};
+
+/// Something in a ROM that is likely a bug, found by `lint_jumps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintWarning {
+    /// The 1NNN at `address` jumps to `target`, outside the ROM.
+    JumpOutOfBounds { address: usize, target: usize },
+    /// The 2NNN at `address` calls `target`, outside the ROM.
+    CallOutOfBounds { address: usize, target: usize },
+}
+
+/// Flags every 1NNN and 2NNN in `rom`, loaded at `load_addr`, that leads
+/// outside the ROM.
+///
+/// Every 2 byte word is checked as if it was code, so sprite data that looks
+/// like a jump can be flagged too.
+pub fn lint_jumps(rom: &[u8], load_addr: usize) -> Vec<LintWarning> {
+    let code = load_addr..load_addr + rom.len();
+    let words = rom
+        .chunks_exact(2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+
+    let mut warnings = Vec::new();
+    for (word, opcode) in words.enumerate() {
+        let address = load_addr + word * 2;
+        let warning = match decode(opcode) {
+            Some(Instruction::Jump(target)) if !code.contains(&target) => {
+                LintWarning::JumpOutOfBounds { address, target }
+            }
+            Some(Instruction::SubRoutine(target)) if !code.contains(&target) => {
+                LintWarning::CallOutOfBounds { address, target }
+            }
+            _ => continue,
+        };
+        warnings.push(warning);
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn jump_past_the_end() {
+        #[rustfmt::skip]
+        let rom = [
+            0x22, 0x06, // CALL 0x206
+            0x12, 0x08, // JP 0x208, just past the end
+            0x11, 0xFE, // JP 0x1FE, before the start
+            0x00, 0xEE, // RET
+        ];
+        let warnings = lint_jumps(&rom, 0x200);
+        assert_eq!(
+            warnings,
+            [
+                LintWarning::JumpOutOfBounds {
+                    address: 0x202,
+                    target: 0x208
+                },
+                LintWarning::JumpOutOfBounds {
+                    address: 0x204,
+                    target: 0x1FE
+                },
+            ]
+        );
+
+        // without the rest of the ROM the call leads nowhere
+        let call = LintWarning::CallOutOfBounds {
+            address: 0x200,
+            target: 0x206,
+        };
+        assert_eq!(lint_jumps(&rom[..2], 0x200), [call]);
+    }
+}
@@ -0,0 +1,72 @@
+use super::Chip8;
+use crate::Quirks;
+
+/// Runs `rom` on two machines, one with quirks `a` and one with `b`, an
+/// instruction at a time and stops at the first one after which they differ.
+///
+/// Returns the cycle count at that point and the address of the instruction
+/// responsible, or `None` if they still agree after `max_cycles`. A fault in
+/// either machine ends the run there and counts as a difference. Both
+/// machines see the same input, which is none, so ROMs that wait for a key
+/// never get past it.
+pub fn lockstep_diverge(
+    rom: &[u8],
+    a: Quirks,
+    b: Quirks,
+    max_cycles: usize,
+) -> Option<(u64, usize)> {
+    let mut machine_a = Chip8::from_bytes(rom);
+    let mut machine_b = Chip8::from_bytes(rom);
+    machine_a.set_quirks(a);
+    machine_b.set_quirks(b);
+
+    for _ in 0..max_cycles {
+        let pc = machine_a.pc();
+        let outcome_a = machine_a.step();
+        let outcome_b = machine_b.step();
+        let faulted = machine_a.fault().is_some() || machine_b.fault().is_some();
+        if faulted || outcome_a != outcome_b {
+            return Some((machine_a.cycle_count(), pc));
+        }
+        let same_path = machine_a.execution_signature() == machine_b.execution_signature();
+        if !same_path || !machine_a.diff(&machine_b).is_empty() {
+            return Some((machine_a.cycle_count(), pc));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diverge_on_shift() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x06, // V0 = 6
+            0x61, 0x01, // V1 = 1
+            0x80, 0x16, // V0 = V1 >> 1 or V0 >> 1
+            0x12, 0x06, // loop
+        ];
+        let vip = Quirks::default();
+        let schip = Quirks {
+            shift_uses_vy: false,
+            ..vip
+        };
+        assert_eq!(lockstep_diverge(&rom, vip, schip, 100), Some((3, 0x204)));
+        assert_eq!(lockstep_diverge(&rom, vip, vip, 100), None);
+        assert_eq!(lockstep_diverge(&rom, vip, schip, 2), None);
+    }
+
+    #[test]
+    fn diverge_on_fault() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x01, // V0 = 1
+            0x00, 0xEE, // return with an empty stack
+        ];
+        let vip = Quirks::default();
+        assert_eq!(lockstep_diverge(&rom, vip, vip, 100), Some((2, 0x202)));
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::vec::Vec;
+
+use super::Chip8;
+use crate::Chip8Error;
+
+impl Chip8 {
+    /// Writes `bytes` to RAM at `addr` like a cheat cartridge would, e.g. to
+    /// turn a `7XFF` that takes a life away into a `7X00`. The patch is
+    /// remembered so `remove_patch` can put the original bytes back.
+    ///
+    /// Patches live in RAM, so a `reset` or `load_state` drops them.
+    pub fn apply_patch(&mut self, addr: usize, bytes: &[u8]) -> Result<(), Chip8Error> {
+        let range = self.ram_range(addr, bytes.len())?;
+        self.patched_over.push(self.ram[range.clone()].to_vec());
+        self.ram[range].copy_from_slice(bytes);
+        self.patches.push((addr, bytes.to_vec()));
+        Ok(())
+    }
+
+    /// The patches applied so far as `(address, bytes)`, oldest first.
+    pub fn patches(&self) -> &[(usize, Vec<u8>)] {
+        &self.patches
+    }
+
+    /// Removes the latest patch applied at `addr`, restoring the bytes that
+    /// were there before it. Returns false if there is no such patch.
+    ///
+    /// Overlapping patches should be removed newest first, otherwise the
+    /// restored bytes include parts of the other patch.
+    pub fn remove_patch(&mut self, addr: usize) -> bool {
+        let latest = self
+            .patches
+            .iter()
+            .rposition(|(address, _)| *address == addr);
+        let Some(index) = latest else {
+            return false;
+        };
+        self.patches.remove(index);
+        let original = self.patched_over.remove(index);
+        self.ram[addr..addr + original.len()].copy_from_slice(&original);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn patched_instruction_runs() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x6003, // V0 = 3, the lives
+            0x70FF, // V0 -= 1
+        ]);
+        emulator.apply_patch(0x203, &[0x00]).unwrap(); // V0 += 0
+        assert_eq!(emulator.patches(), [(0x203, vec![0x00])]);
+        emulator.step();
+        emulator.step();
+        assert_eq!(emulator.v[0], 3);
+
+        assert!(emulator.remove_patch(0x203));
+        assert!(!emulator.remove_patch(0x203));
+        assert!(emulator.patches().is_empty());
+        assert_eq!(emulator.read_memory(0x202, 2), Ok(&[0x70, 0xFF][..]));
+
+        let err = emulator.apply_patch(0xFFF, &[0, 0]).unwrap_err();
+        assert_eq!(
+            err,
+            Chip8Error::MemoryOutOfBounds {
+                address: 0xFFF,
+                len: 2
+            }
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use super::Chip8;
+
+/// Which of the ambiguous behaviours the interpreter currently follows.
+///
+/// These are the same checks the quirks test of the
+/// [Timendus test suite](https://github.com/Timendus/chip8-test-suite) displays,
+/// but instead of parsing its screen every check runs a tiny probe program,
+/// so the result doesn't depend on having the test ROM around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuirkReport {
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub reset_vf_on_logic: bool,
+    /// FX55 and FX65 leave I pointing after the last register
+    pub load_store_increments_i: bool,
+    /// sprites are cut at the screen edge instead of wrapping around
+    pub clip_sprites: bool,
+    /// 8XY6 and 8XYE shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    /// BNNN is BXNN, jumping to XNN + VX instead of NNN + V0
+    pub jump_with_vx: bool,
+}
+
+impl Chip8 {
+    pub fn quirk_report(&self) -> QuirkReport {
+        QuirkReport {
+            reset_vf_on_logic: self.probe(&[0x6F05, 0x8011]).v[0xF] == 0,
+            load_store_increments_i: self.probe(&[0xA300, 0xF255]).i == 0x303,
+            // the 0 glyph drawn at x = 62 wraps to x = 0 and 1 if not clipped
+            clip_sprites: self.probe(&[0xF029, 0x603E, 0xD015]).screen[0][0] == 0,
+            shift_uses_vy: self.probe(&[0x6001, 0x6102, 0x8016]).v[0] == 1,
+            jump_with_vx: self.probe(&[0x6304, 0xB310]).pc == 0x314,
+        }
+    }
+
+    /// Runs `opcodes` on a blank machine configured like this one.
+    fn probe(&self, opcodes: &[u16]) -> Chip8 {
+        let mut machine = Chip8::blank();
+        machine.quirks = self.quirks;
+        for opcode in opcodes {
+            machine.run_opcode(*opcode);
+        }
+        machine
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Quirks;
+
+    #[test]
+    fn report_current_quirks() {
+        let emulator = Chip8::blank();
+        assert_eq!(
+            emulator.quirk_report(),
+            QuirkReport {
+                reset_vf_on_logic: true,
+                load_store_increments_i: true,
+                clip_sprites: true,
+                shift_uses_vy: true,
+                jump_with_vx: false,
+            }
+        );
+        let mut emulator = Chip8::blank();
+        emulator.set_quirks(Quirks {
+            shift_uses_vy: false,
+            jump_with_vx: true,
+            ..Quirks::default()
+        });
+        let report = emulator.quirk_report();
+        assert!(!report.shift_uses_vy && report.jump_with_vx);
+    }
+}
@@ -0,0 +1,92 @@
+#[cfg(feature = "std")]
+use alloc::format;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+use super::Chip8;
+#[cfg(feature = "std")]
+use crate::Chip8Error;
+
+impl Chip8 {
+    /// The RPL user flags FX75 saved, SCHIP high scores usually end up here.
+    pub fn rpl_flags(&self) -> &[u8; 16] {
+        &self.rpl_flags
+    }
+
+    /// Keeps the RPL flags in `path`, so they survive between runs. The flags
+    /// are read from it right away if it exists and written back by `shutdown`.
+    #[cfg(feature = "std")]
+    pub fn set_rpl_file(&mut self, path: impl Into<PathBuf>) -> Result<(), Chip8Error> {
+        let path = path.into();
+        match std::fs::read(&path) {
+            Ok(flags) => {
+                let len = flags.len().min(self.rpl_flags.len());
+                self.rpl_flags = [0; 16];
+                self.rpl_flags[..len].copy_from_slice(&flags[..len]);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => return Err(io_error(&path, err)),
+        }
+        self.rpl_file = Some(path);
+        Ok(())
+    }
+
+    /// Saves what has to outlive the machine before it goes away: the RPL
+    /// flags when `set_rpl_file` was called, and the rest of a trace started
+    /// with `start_trace_file`, see `stop_trace_file`.
+    #[cfg(feature = "std")]
+    pub fn shutdown(&mut self) -> Result<(), Chip8Error> {
+        // the trace is finished even if the flags can't be written
+        let trace = self.stop_trace_file();
+        if let Some(path) = &self.rpl_file {
+            std::fs::write(path, self.rpl_flags).map_err(|err| io_error(path, err))?;
+        }
+        trace
+    }
+}
+
+#[cfg(feature = "std")]
+fn io_error(path: &std::path::Path, err: std::io::Error) -> Chip8Error {
+    let message = format!("{}: {err}", path.display());
+    Chip8Error::Io { message }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flags_persist_through_shutdown() {
+        let path = std::env::temp_dir().join(format!("chip8-rpl-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let name = format!("chip8-rpl-trace-{}", std::process::id());
+        let trace = std::env::temp_dir().join(name);
+        let trace = trace.to_str().unwrap();
+
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x6005, // V0 = 5
+            0x6107, // V1 = 7
+            0xF175, // save V0..V1 to the flags
+        ]);
+        emulator.set_rpl_file(&path).unwrap();
+        emulator.start_trace_file(trace).unwrap();
+        for _ in 0..3 {
+            emulator.step();
+        }
+        emulator.reset();
+        assert_eq!(emulator.rpl_flags()[..3], [5, 7, 0]);
+        emulator.shutdown().unwrap();
+
+        // the trace was still buffered until shutdown
+        let lines = std::fs::read_to_string(trace).unwrap();
+        std::fs::remove_file(trace).unwrap();
+        assert_eq!(lines.lines().count(), 3);
+
+        let mut next_run = Chip8::from_opcodes(&[0xF185]);
+        next_run.set_rpl_file(&path).unwrap();
+        next_run.step();
+        assert_eq!(next_run.registers()[..2], [5, 7]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
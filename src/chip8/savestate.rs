@@ -0,0 +1,327 @@
+use alloc::vec::Vec;
+
+use super::Chip8;
+use crate::Chip8Error;
+
+// Layout, every multi-byte value is little-endian whatever the host is:
+//   magic "CH8S", version (u8), RAM size (u32),
+//   V0..VF, I (u16), PC (u16), SP (u8), stack (16 * u16),
+//   delay, sound, pitch (u8), audio pattern (16), rng (u32),
+//   waiting for key (u8), released key (u8, 0xFF for none),
+//   high-res (u8), selected planes (u8), packed framebuffer of the first
+//   and then the second plane (256 or 1024 in high-res each), RAM,
+//   CRC-32 of everything before it (u32)
+const MAGIC: &[u8; 4] = b"CH8S";
+const VERSION: u8 = 5;
+
+/// The common CRC-32 (IEEE 802.3, as used by zip and PNG).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Reads the fields of a save state in order, failing once the data runs out.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.data.len() < len {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Chip8Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+impl Chip8 {
+    /// Snapshot of the whole machine, multi-byte values are little-endian.
+    ///
+    /// The ROM itself and the keypad state are not part of the snapshot. A
+    /// checksum at the end lets `load_state` notice damaged snapshots.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&(self.ram.len() as u32).to_le_bytes());
+
+        data.extend_from_slice(&self.v);
+        data.extend_from_slice(&self.i.to_le_bytes());
+        data.extend_from_slice(&(self.pc as u16).to_le_bytes());
+        data.push(self.sp as u8);
+        for address in self.stack {
+            data.extend_from_slice(&(address as u16).to_le_bytes());
+        }
+
+        data.push(self.deelay);
+        data.push(self.sound_timer);
+        data.push(self.audio_pitch);
+        data.extend_from_slice(&self.audio_pattern);
+        data.extend_from_slice(&self.rng.to_le_bytes());
+        data.push(self.waiting_for_key as u8);
+        data.push(self.released_key.unwrap_or(0xFF));
+
+        data.push(self.hires as u8);
+        data.push(self.planes);
+        data.extend_from_slice(&self.pack_screen(1));
+        data.extend_from_slice(&self.pack_screen(2));
+        data.extend_from_slice(&self.ram);
+        data.extend_from_slice(&crc32(&data).to_le_bytes());
+        data
+    }
+
+    /// Restores a snapshot made by `save_state`, the machine is left untouched on error.
+    ///
+    /// Fails with `CorruptSaveState` if the checksum doesn't match, which is
+    /// checked before anything else is read, so a damaged or truncated
+    /// snapshot is reported as such whichever byte got hit.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let mut reader = Reader { data };
+        if reader.take(MAGIC.len())? != MAGIC || reader.u8()? != VERSION {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+        // the fields, then the checksum over everything before it
+        let Some(len) = reader.data.len().checked_sub(4) else {
+            return Err(Chip8Error::InvalidSaveState);
+        };
+        let (fields, checksum) = reader.data.split_at(len);
+        let checksum = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+        if checksum != crc32(&data[..data.len() - 4]) {
+            return Err(Chip8Error::CorruptSaveState);
+        }
+        let mut reader = Reader { data: fields };
+
+        let mut state = Chip8::blank();
+        let ram_size = reader.u32()? as usize;
+        if ram_size != state.ram.len() {
+            return Err(Chip8Error::SaveStateMismatch {
+                expected: state.ram.len(),
+                found: ram_size,
+            });
+        }
+
+        state.v.copy_from_slice(reader.take(16)?);
+        state.i = reader.u16()?;
+        state.pc = reader.u16()? as usize;
+        state.sp = reader.u8()? as usize;
+        for address in state.stack.iter_mut() {
+            *address = reader.u16()? as usize;
+        }
+        if state.sp > state.stack.len() || state.pc + 1 >= state.ram.len() {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+
+        state.deelay = reader.u8()?;
+        state.sound_timer = reader.u8()?;
+        state.audio_pitch = reader.u8()?;
+        state.audio_pattern.copy_from_slice(reader.take(16)?);
+        state.rng = reader.u32()?;
+        state.waiting_for_key = reader.u8()? != 0;
+        state.released_key = match reader.u8()? {
+            0xFF => None,
+            key => Some(key & 0xF),
+        };
+
+        state.hires = reader.u8()? != 0;
+        state.planes = reader.u8()? & 0b11;
+        let (width, height) = (state.width(), state.height());
+        state.unpack_screen(reader.take(width * height / 8)?, 1);
+        state.unpack_screen(reader.take(width * height / 8)?, 2);
+        let ram = reader.take(state.ram.len())?;
+        state.ram.copy_from_slice(ram);
+        if !reader.data.is_empty() {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+
+        state.screen_update = true;
+        state.keep_settings(self);
+        *self = state;
+        Ok(())
+    }
+
+    /// Snapshot of just the display, in the `framebuffer_packed` format.
+    pub fn save_display(&self) -> Vec<u8> {
+        self.framebuffer_packed()
+    }
+
+    /// Puts a display saved by `save_display` back on screen, on the first
+    /// plane. The resolution follows from the size: 256 bytes are low-res,
+    /// 1024 are SCHIP high-res.
+    pub fn load_display(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        self.hires = match data.len() {
+            256 => false,
+            1024 => true,
+            len => return Err(Chip8Error::InvalidDisplay { len }),
+        };
+        self.screen = [[0; 128]; 64];
+        self.unpack_screen(data, 1);
+        self.screen_update = true;
+        Ok(())
+    }
+
+    /// Lights `plane` in the active part of the screen wherever the packed
+    /// framebuffer has a pixel set, the screen is expected to start dark.
+    fn unpack_screen(&mut self, packed: &[u8], plane: u8) {
+        let (width, height) = (self.width(), self.height());
+        for (y, row) in self.screen[..height].iter_mut().enumerate() {
+            for (x, pixel) in row[..width].iter_mut().enumerate() {
+                if packed[(y * width + x) / 8] & (0x80 >> (x % 8)) != 0 {
+                    *pixel |= plane;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut emulator =
+            Chip8::from_bytes(&[0x6A, 0x42, 0x22, 0x06, 0x00, 0x00, 0xF0, 0x29, 0xD0, 0x15]);
+        for _ in 0..4 {
+            emulator.start_cycle();
+        }
+        let state = emulator.save_state();
+
+        let mut restored = Chip8::blank();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.v, emulator.v);
+        assert_eq!(restored.i, emulator.i);
+        assert_eq!(restored.pc, emulator.pc);
+        assert_eq!(restored.sp, 1);
+        assert_eq!(restored.stack, emulator.stack);
+        assert_eq!(restored.screen, emulator.screen);
+        assert_eq!(restored.ram, emulator.ram);
+    }
+
+    #[test]
+    fn save_and_load_display() {
+        let mut emulator = Chip8::blank();
+        for (digit, x) in [(0, 0), (1, 6), (2, 60)] {
+            emulator.v[0] = digit;
+            emulator.run_opcode(0xF029); // I = font glyph V0
+            emulator.v[1] = x;
+            emulator.run_opcode(0xD125); // draw at (V1, 0)
+        }
+        let screen = emulator.screen;
+        let display = emulator.save_display();
+        assert_eq!(display.len(), 256);
+
+        emulator.run_opcode(0x00E0);
+        emulator.run_opcode(0x00FF);
+        emulator.load_display(&display).unwrap();
+        assert!(!emulator.is_hires());
+        assert_eq!(emulator.screen, screen);
+
+        let err = emulator.load_display(&display[1..]).unwrap_err();
+        assert_eq!(err, Chip8Error::InvalidDisplay { len: 255 });
+    }
+
+    #[test]
+    fn fixed_header_layout() {
+        let mut emulator = Chip8::from_bytes(&[0x61, 0xAB, 0xA3, 0x21, 0x22, 0x10]);
+        for _ in 0..3 {
+            emulator.start_cycle();
+        }
+        let state = emulator.save_state();
+
+        assert_eq!(&state[0..4], b"CH8S");
+        assert_eq!(state[4], 5);
+        assert_eq!(state[5..9], [0x00, 0x10, 0x00, 0x00]); // 4096 bytes of RAM
+        assert_eq!(state[9 + 1], 0xAB); // V1
+        assert_eq!(state[25..27], [0x21, 0x03]); // I = 0x321
+        assert_eq!(state[27..29], [0x10, 0x02]); // PC = 0x210
+        assert_eq!(state[29], 1); // SP
+        assert_eq!(state[30..32], [0x06, 0x02]); // return address 0x206
+    }
+
+    /// Puts a checksum that matches the edited `state` back at its end.
+    fn fix_checksum(state: &mut [u8]) {
+        let len = state.len() - 4;
+        let checksum = crc32(&state[..len]);
+        state[len..].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    #[test]
+    fn load_rejects_other_ram_size() {
+        let mut emulator = Chip8::blank();
+        let mut state = emulator.save_state();
+        state[5..9].copy_from_slice(&0x10000u32.to_le_bytes());
+        fix_checksum(&mut state);
+        assert_eq!(
+            emulator.load_state(&state),
+            Err(Chip8Error::SaveStateMismatch {
+                expected: 0x1000,
+                found: 0x10000
+            })
+        );
+    }
+
+    #[test]
+    fn load_rejects_corrupt_state() {
+        let mut emulator = Chip8::from_bytes(&[0x6A, 0x42]);
+        let mut state = emulator.save_state();
+        assert_eq!(emulator.load_state(&state), Ok(()));
+
+        state[0x400] ^= 0x10; // somewhere in RAM
+        assert_eq!(
+            emulator.load_state(&state),
+            Err(Chip8Error::CorruptSaveState)
+        );
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        // header fields that are checked on their own, or change how much
+        // is read after them, still count as damage
+        let hires = 9 + 16 + 2 + 2 + 1 + 32 + 3 + 16 + 4 + 2;
+        for (offset, flip) in [(5, 0x01), (27, 0x80), (29, 0x40), (hires, 0x01)] {
+            let mut state = emulator.save_state();
+            state[offset] ^= flip;
+            assert_eq!(
+                emulator.load_state(&state),
+                Err(Chip8Error::CorruptSaveState),
+                "byte {offset}"
+            );
+        }
+    }
+
+    #[test]
+    fn load_rejects_truncated_state() {
+        let mut emulator = Chip8::from_bytes(&[0x6A, 0x42]);
+        let state = emulator.save_state();
+        assert_eq!(
+            emulator.load_state(&state[..state.len() - 1]),
+            Err(Chip8Error::CorruptSaveState)
+        );
+        assert_eq!(
+            emulator.load_state(&state[..5]),
+            Err(Chip8Error::InvalidSaveState)
+        );
+        assert_eq!(emulator.load_state(&[]), Err(Chip8Error::InvalidSaveState));
+    }
+}
@@ -0,0 +1,110 @@
+use super::{Chip8, Chip8Event, Instruction};
+
+/// Mask of the registers `0..=last`.
+fn up_to(last: u8) -> u16 {
+    u16::MAX >> (15 - (last & 0xF))
+}
+
+fn bit(register: u8) -> u16 {
+    1 << (register & 0xF)
+}
+
+impl Instruction {
+    /// The registers an arithmetic or draw instruction uses as input.
+    fn arithmetic_reads(self) -> u16 {
+        use Instruction::*;
+        match self {
+            AddToNormalRegister(x, _) | AddRegisterToIndex(x) => bit(x),
+            SetXtoY(_, y) => bit(y),
+            SetXtoXorY(x, y)
+            | SetXtoXandY(x, y)
+            | SetXtoXxorY(x, y)
+            | AddYtoX(x, y)
+            | SubYfromX(x, y)
+            | SetXtoYshiftRightOnce(x, y)
+            | SetXtoYMinusX(x, y)
+            | SetXtoYshiftLeftOnce(x, y)
+            | DrawSprite(x, y, _) => bit(x) | bit(y),
+            _ => 0,
+        }
+    }
+
+    /// The registers the instruction can write to.
+    fn writes(self) -> u16 {
+        use Instruction::*;
+        match self {
+            LoadNormalRegister(x, _)
+            | AddToNormalRegister(x, _)
+            | LoadRegisterWithRandom(x, _)
+            | StoreDeelayInRegister(x)
+            | WaitUserInput(x) => bit(x),
+            // 8XY0 leaves VF alone, the rest of 8XYN can set it
+            SetXtoY(x, _) => bit(x),
+            SetXtoXorY(x, _)
+            | SetXtoXandY(x, _)
+            | SetXtoXxorY(x, _)
+            | AddYtoX(x, _)
+            | SubYfromX(x, _)
+            | SetXtoYshiftRightOnce(x, _)
+            | SetXtoYMinusX(x, _)
+            | SetXtoYshiftLeftOnce(x, _) => bit(x) | bit(0xF),
+            DrawSprite(..) => bit(0xF),
+            FillRegisters(last) | LoadFlags(last) => up_to(last),
+            _ => 0,
+        }
+    }
+}
+
+impl Chip8 {
+    /// Reports a `Chip8Event::UninitRegisterRead` whenever an arithmetic or
+    /// draw instruction reads a register nothing wrote to since the last
+    /// reset. Registers do start at 0, but a ROM relying on that is often a
+    /// bug, and some interpreters start with garbage. Off by default.
+    pub fn set_track_uninit_registers(&mut self, track: bool) {
+        self.track_uninit_registers = track;
+    }
+
+    pub(super) fn track_registers(&mut self, instruction: Instruction) {
+        let uninit = instruction.arithmetic_reads() & !self.written_registers;
+        for register in 0..16 {
+            if uninit & bit(register) != 0 {
+                self.events.push(Chip8Event::UninitRegisterRead(register));
+            }
+        }
+        self.written_registers |= instruction.writes();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_untouched_register() {
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x6005, // V0 = 5
+            0x8014, // V0 += V1, V1 was never written
+            0x8014, // reported again, V1 still wasn't written
+            0x7201, // V2 += 1
+        ]);
+        emulator.set_track_uninit_registers(true);
+        for _ in 0..4 {
+            emulator.step();
+        }
+        assert_eq!(
+            emulator.drain_events(),
+            [
+                Chip8Event::UninitRegisterRead(1),
+                Chip8Event::UninitRegisterRead(1),
+                Chip8Event::UninitRegisterRead(2),
+            ]
+        );
+
+        emulator.reset();
+        emulator.set_track_uninit_registers(false);
+        emulator.step();
+        emulator.step();
+        assert!(emulator.drain_events().is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::{Chip8, StepOutcome};
+use crate::Chip8Error;
+
+impl Chip8 {
+    /// Writes a line to `path` for every instruction executed from now on,
+    /// with its address, opcode, disassembly and the registers it changed.
+    ///
+    /// ```text
+    /// 0202  7003  ADD V0, 0x03  V0 05->08
+    /// ```
+    ///
+    /// The file is replaced if it exists and kept open until
+    /// `stop_trace_file` or `shutdown`, across resets. A write that fails stops tracing and
+    /// the machine, with the error as its `fault`.
+    pub fn start_trace_file(&mut self, path: &str) -> Result<(), Chip8Error> {
+        let file = File::create(path).map_err(|err| Chip8Error::Io {
+            message: format!("{path}: {err}"),
+        })?;
+        self.trace_file = Some(BufWriter::new(file));
+        self.trace_remaining = None;
+        Ok(())
+    }
+
+    /// Like `start_trace_file`, but only the next `cycles` instructions are
+    /// written, then tracing stops by itself. Handy for the startup of a
+    /// game without a trace of everything it does after.
+    pub fn trace_first_cycles(&mut self, cycles: u64, path: &str) -> Result<(), Chip8Error> {
+        self.start_trace_file(path)?;
+        if cycles == 0 {
+            self.stop_trace_file()?;
+        }
+        self.trace_remaining = Some(cycles);
+        Ok(())
+    }
+
+    /// Stops tracing and flushes what's left to the file.
+    pub fn stop_trace_file(&mut self) -> Result<(), Chip8Error> {
+        match self.trace_file.take() {
+            Some(mut file) => file.flush().map_err(trace_error),
+            None => Ok(()),
+        }
+    }
+
+    /// `before` are the registers and I before the instruction ran.
+    pub(super) fn write_trace(&mut self, outcome: &StepOutcome, before: ([u8; 16], u16)) {
+        let mut line = format!("{:04X}  {:04X}  ", outcome.pc, outcome.opcode);
+        let _ = match outcome.instruction {
+            Some(instruction) => write!(line, "{instruction}"),
+            None => write!(line, "DW 0x{:04X}", outcome.opcode),
+        };
+        let (registers, index) = before;
+        for (register, (old, new)) in registers.iter().zip(self.v).enumerate() {
+            if *old != new {
+                let _ = write!(line, "  V{register:X} {old:02X}->{new:02X}");
+            }
+        }
+        if index != self.i {
+            let _ = write!(line, "  I {index:03X}->{:03X}", self.i);
+        }
+
+        let Some(file) = &mut self.trace_file else {
+            return;
+        };
+        if let Err(err) = writeln!(file, "{line}") {
+            self.trace_file = None;
+            self.fault = Some(trace_error(err));
+            return;
+        }
+        if let Some(remaining) = &mut self.trace_remaining {
+            *remaining -= 1;
+            if *remaining == 0 {
+                if let Err(err) = self.stop_trace_file() {
+                    self.fault = Some(err);
+                }
+            }
+        }
+    }
+}
+
+fn trace_error(err: std::io::Error) -> Chip8Error {
+    Chip8Error::Io {
+        message: format!("trace file: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trace_to_file() {
+        let path = std::env::temp_dir().join(format!("chip8-trace-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        #[rustfmt::skip]
+        let mut emulator = Chip8::from_opcodes(&[
+            0x6005, // V0 = 5
+            0x7003, // V0 += 3
+            0xA300, // I = 0x300
+            0x1206, // loop
+        ]);
+        emulator.start_trace_file(path).unwrap();
+        for _ in 0..5 {
+            emulator.step();
+        }
+        emulator.stop_trace_file().unwrap();
+        emulator.step();
+
+        let trace = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let lines: Vec<_> = trace.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "0200  6005  LD V0, 0x05  V0 00->05",
+                "0202  7003  ADD V0, 0x03  V0 05->08",
+                "0204  A300  LD I, 0x300  I 000->300",
+                "0206  1206  JP 0x206",
+                "0206  1206  JP 0x206",
+            ]
+        );
+    }
+
+    #[test]
+    fn trace_only_the_start() {
+        let name = format!("chip8-trace-start-{}", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let path = path.to_str().unwrap();
+
+        let mut emulator = Chip8::from_opcodes(&[0x7001, 0x1200]);
+        emulator.trace_first_cycles(3, path).unwrap();
+        for _ in 0..10 {
+            emulator.step();
+        }
+        let trace = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(trace.lines().count(), 3);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn trace_write_fails() {
+        // /dev/full takes the buffered lines and fails the flush
+        let mut emulator = Chip8::from_opcodes(&[0x7001, 0x1200]);
+        emulator.start_trace_file("/dev/full").unwrap();
+        emulator.step();
+        let err = emulator.stop_trace_file().unwrap_err();
+        assert!(matches!(err, Chip8Error::Io { .. }));
+
+        emulator.trace_first_cycles(2, "/dev/full").unwrap();
+        emulator.step();
+        assert_eq!(emulator.fault(), None);
+        emulator.step();
+        assert_eq!(emulator.fault(), Some(&err));
+        assert!(emulator.step().is_none());
+    }
+}
@@ -0,0 +1,205 @@
+use chip8::runner::{Pacer, RunMode};
+use chip8::Chip8;
+use std::fmt;
+
+/// What the binary was asked to do.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Pick a ROM from the roms folder and run it in a window.
+    Window,
+    /// `chip8 run <rom> --cycles <n> [--dump-screen]`, no window.
+    Run {
+        rom: String,
+        cycles: u64,
+        dump_screen: bool,
+    },
+    /// `chip8 debug <rom>`, an interactive debugger on stdin and stdout.
+    Debug { rom: String },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CliError {
+    Usage(String),
+    Failed(String),
+}
+
+impl CliError {
+    /// Exit code for the process, 2 for bad arguments like most tools.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => 2,
+            CliError::Failed(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{message}\n{USAGE}"),
+            CliError::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+const USAGE: &str = "usage: chip8 run <rom> --cycles <n> [--dump-screen]
+       chip8 debug <rom>";
+
+/// Parses the arguments following the program name.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Mode, CliError> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        None => return Ok(Mode::Window),
+        Some("run") => {}
+        Some("debug") => {
+            let rom = args
+                .next()
+                .ok_or_else(|| CliError::Usage(String::from("no ROM given")))?;
+            if let Some(arg) = args.next() {
+                return Err(CliError::Usage(format!("unexpected argument `{arg}`")));
+            }
+            return Ok(Mode::Debug { rom });
+        }
+        Some(other) => return Err(CliError::Usage(format!("unknown command `{other}`"))),
+    }
+
+    let mut rom = None;
+    let mut cycles = None;
+    let mut dump_screen = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cycles" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| CliError::Usage(String::from("--cycles needs a value")))?;
+                let value = value
+                    .parse()
+                    .map_err(|_| CliError::Usage(format!("invalid cycle count `{value}`")))?;
+                cycles = Some(value);
+            }
+            "--dump-screen" => dump_screen = true,
+            flag if flag.starts_with("--") => {
+                return Err(CliError::Usage(format!("unknown option `{flag}`")))
+            }
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{arg}`"))),
+        }
+    }
+
+    Ok(Mode::Run {
+        rom: rom.ok_or_else(|| CliError::Usage(String::from("no ROM given")))?,
+        cycles: cycles.ok_or_else(|| CliError::Usage(String::from("--cycles is required")))?,
+        dump_screen,
+    })
+}
+
+/// Runs `cycles` instructions of `rom` without a window, counting the timers
+/// down once per frame worth of instructions, as fast as it can. Returns what
+/// should be printed, or fails as soon as an instruction faults.
+pub fn run_headless(rom: &str, cycles: u64, dump_screen: bool) -> Result<String, CliError> {
+    let mut emulator = load(rom)?;
+    let mut pacer = Pacer::new(RunMode::Unthrottled);
+    for _ in 0..cycles {
+        if step(&mut emulator) {
+            pacer.wait();
+        }
+        if let Some(fault) = emulator.fault() {
+            let pc = emulator.pc();
+            let message = format!("{rom}: {fault}, stopped at {pc:#05X}");
+            return Err(CliError::Failed(message));
+        }
+    }
+
+    if dump_screen {
+        Ok(emulator.screen_to_string())
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// Reads the ROM at `path` into a new machine.
+pub fn load(path: &str) -> Result<Chip8, CliError> {
+    let data =
+        std::fs::read(path).map_err(|err| CliError::Failed(format!("can't read {path}: {err}")))?;
+    Chip8::try_from_bytes(&data).map_err(|err| CliError::Failed(format!("{path}: {err}")))
+}
+
+/// Executes one instruction, counting the timers down after every frame
+/// worth of instructions as there is no 60hz clock without a window.
+///
+/// Returns whether that ended a frame. Does nothing once the machine stopped
+/// on a fault, see `Chip8::fault`.
+pub fn step(emulator: &mut Chip8) -> bool {
+    if emulator.step().is_none() {
+        return false;
+    }
+    let cycles_per_frame = emulator.cycles_per_frame() as u64;
+    let frame_done = emulator.cycle_count().is_multiple_of(cycles_per_frame);
+    if frame_done {
+        emulator.tick();
+    }
+    frame_done
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parse_run() {
+        assert_eq!(parse_args(args("")), Ok(Mode::Window));
+        assert_eq!(
+            parse_args(args("run roms/x.ch8 --cycles 5000 --dump-screen")),
+            Ok(Mode::Run {
+                rom: String::from("roms/x.ch8"),
+                cycles: 5000,
+                dump_screen: true,
+            })
+        );
+        assert!(matches!(
+            parse_args(args("run roms/x.ch8")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse_args(args("run roms/x.ch8 --cycles lots")),
+            Err(CliError::Usage(_))
+        ));
+        assert_eq!(
+            parse_args(args("debug roms/x.ch8")),
+            Ok(Mode::Debug {
+                rom: String::from("roms/x.ch8")
+            })
+        );
+        assert!(matches!(parse_args(args("fly")), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn headless_run() {
+        let screen = run_headless("roms/ibm.ch8", 200, true).unwrap();
+        assert_eq!(screen.lines().count(), 32);
+        assert!(screen.contains('#'));
+
+        let err = run_headless("roms/missing.ch8", 10, true).unwrap_err();
+        assert_eq!(err.exit_code(), 1);
+        assert!(err.to_string().starts_with("can't read roms/missing.ch8"));
+    }
+
+    #[test]
+    fn headless_fault() {
+        let path = std::env::temp_dir().join(format!("chip8-ret-{}.ch8", std::process::id()));
+        let path = path.to_str().unwrap();
+        // a return with an empty stack
+        std::fs::write(path, [0x00, 0xEE]).unwrap();
+        let err = run_headless(path, 10, false).unwrap_err();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(err.exit_code(), 1);
+        assert_eq!(
+            err.to_string(),
+            format!("{path}: return with an empty stack, stopped at 0x202")
+        );
+    }
+}
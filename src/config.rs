@@ -0,0 +1,203 @@
+use crate::{Chip8Builder, Quirks};
+use macroquad::color::Color;
+use std::fmt;
+
+/// Settings for one ROM, read from a sidecar file next to it, see
+/// `load_rom_settings`. Anything the file doesn't mention keeps its default.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Config {
+    pub quirks: Quirks,
+    /// Instructions per second, the builder's default when `None`.
+    pub clock_hz: Option<u32>,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+/// Line `line` (from 1) of a settings file can't be understood.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the small part of TOML the settings need: `key = value` lines,
+    /// `#` comments and a `[quirks]` table.
+    ///
+    /// ```toml
+    /// clock_hz = 700
+    /// foreground = "#FFB000"
+    ///
+    /// [quirks]
+    /// clip_sprites = false
+    /// ```
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        let mut in_quirks = false;
+        for (index, line) in text.lines().enumerate() {
+            let error = |reason| ConfigError {
+                line: index + 1,
+                reason,
+            };
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(table) = line.strip_prefix('[') {
+                let table = table.strip_suffix(']').ok_or(error("expected ]"))?;
+                in_quirks = match table.trim() {
+                    "quirks" => true,
+                    _ => return Err(error("unknown table")),
+                };
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or(error("expected key = value"))?;
+            let (key, value) = (key.trim(), value.trim());
+            if in_quirks {
+                let quirk = quirk_mut(&mut config.quirks, key).ok_or(error("unknown quirk"))?;
+                *quirk = value.parse().map_err(|_| error("expected true or false"))?;
+                continue;
+            }
+            match key {
+                "clock_hz" => {
+                    let hz = value.parse().map_err(|_| error("expected a number"))?;
+                    config.clock_hz = Some(hz);
+                }
+                "foreground" | "background" => {
+                    let color = parse_color(value).ok_or(error("expected \"#RRGGBB\""))?;
+                    match key {
+                        "foreground" => config.foreground = Some(color),
+                        _ => config.background = Some(color),
+                    }
+                }
+                _ => return Err(error("unknown setting")),
+            }
+        }
+        Ok(config)
+    }
+
+    /// A builder with these quirks and clock, to make the machine from.
+    pub fn builder(&self) -> Chip8Builder {
+        let builder = Chip8Builder::default().quirks(self.quirks);
+        match self.clock_hz {
+            Some(hz) => builder.clock_hz(hz),
+            None => builder,
+        }
+    }
+}
+
+/// The quirk called `name` in `Quirks::describe`.
+pub(crate) fn quirk_mut<'a>(quirks: &'a mut Quirks, name: &str) -> Option<&'a mut bool> {
+    match name {
+        "lores_wide_sprites" => Some(&mut quirks.lores_wide_sprites),
+        "keep_screen_on_resolution_switch" => Some(&mut quirks.keep_screen_on_resolution_switch),
+        "clip_sprites" => Some(&mut quirks.clip_sprites),
+        "shift_uses_vy" => Some(&mut quirks.shift_uses_vy),
+        "reset_vf_on_logic" => Some(&mut quirks.reset_vf_on_logic),
+        "load_store_increments_i" => Some(&mut quirks.load_store_increments_i),
+        "jump_with_vx" => Some(&mut quirks.jump_with_vx),
+        _ => None,
+    }
+}
+
+/// The line up to a `#` that isn't in a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..index],
+            _ => (),
+        }
+    }
+    line
+}
+
+/// `"#RRGGBB"`, quotes included.
+fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.strip_prefix("\"#")?.strip_suffix('"')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some(Color::from_hex(rgb))
+}
+
+/// The settings in `<rom_path>.toml`, e.g. `roms/pong.ch8.toml`, or `None`
+/// if there is no such file. A file that can't be read or parsed is
+/// reported on stderr and ignored, the game still runs with the defaults.
+pub fn load_rom_settings(rom_path: &str) -> Option<Config> {
+    let path = format!("{rom_path}.toml");
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            eprintln!("{path}: {err}");
+            return None;
+        }
+    };
+    match Config::parse(&text) {
+        Ok(config) => Some(config),
+        Err(err) => {
+            eprintln!("{path}: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sidecar_applies() {
+        let dir = std::env::temp_dir();
+        let rom = dir.join(format!("chip8-settings-{}.ch8", std::process::id()));
+        let rom = rom.to_str().unwrap();
+        std::fs::write(rom, [0x12, 0x00]).unwrap();
+        assert_eq!(load_rom_settings(rom), None);
+
+        let sidecar = "# pong needs a slower clock
+clock_hz = 500
+background = \"#102030\" # dark blue
+
+[quirks]
+clip_sprites = false
+";
+        std::fs::write(format!("{rom}.toml"), sidecar).unwrap();
+        let config = load_rom_settings(rom).unwrap();
+        assert!(!config.quirks.clip_sprites);
+        assert!(config.quirks.shift_uses_vy);
+        assert_eq!(config.background, Some(Color::from_hex(0x102030)));
+        assert_eq!(config.foreground, None);
+
+        let emulator = config.builder().build(&[0x12, 0x00]).unwrap();
+        assert_eq!(emulator.cycles_per_frame(), 8);
+        assert_eq!(emulator.quirks(), config.quirks);
+        std::fs::remove_file(rom).unwrap();
+        std::fs::remove_file(format!("{rom}.toml")).unwrap();
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let err = Config::parse("clock_hz = 500\nspeed = 3").unwrap_err();
+        assert_eq!(
+            err,
+            ConfigError {
+                line: 2,
+                reason: "unknown setting"
+            }
+        );
+        let err = Config::parse("[quirks]\nclip_sprites = yes").unwrap_err();
+        assert_eq!(err.reason, "expected true or false");
+    }
+}
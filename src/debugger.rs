@@ -0,0 +1,194 @@
+use crate::cli;
+use chip8::{disassemble, Chip8};
+use std::io::{self, BufRead, Write};
+
+/// `continue` gives up after this many instructions without hitting a breakpoint.
+const CONTINUE_LIMIT: u64 = 1_000_000;
+
+const HELP: &str = "commands:
+  step [n]          execute n instructions (default 1)
+  continue          run until a breakpoint
+  break <addr>      stop when PC reaches addr
+  regs              show the registers
+  mem <addr> <len>  hex dump of memory
+  disasm [n]        disassemble n instructions at PC (default 10)
+  reset             restart the ROM, breakpoints are kept
+  quit";
+
+/// Reads debugger commands from `input` until it ends or `quit`, printing
+/// the results to `output`.
+pub fn run(mut emulator: Chip8, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    let mut breakpoints = Vec::new();
+
+    write!(output, "> ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            write!(output, "> ")?;
+            output.flush()?;
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        match (command, args.as_slice()) {
+            ("step" | "s", []) => step(&mut emulator, 1, output)?,
+            ("step" | "s", [count]) => match parse_number(count) {
+                Some(count) => step(&mut emulator, count as u64, output)?,
+                None => writeln!(output, "invalid count `{count}`")?,
+            },
+            ("continue" | "c", []) => {
+                let mut executed = 0;
+                loop {
+                    cli::step(&mut emulator);
+                    executed += 1;
+                    if let Some(fault) = emulator.fault() {
+                        writeln!(output, "stopped: {fault}")?;
+                        break;
+                    }
+                    if breakpoints.contains(&emulator.pc()) {
+                        writeln!(output, "breakpoint at {:#05X}", emulator.pc())?;
+                        break;
+                    }
+                    if executed == CONTINUE_LIMIT {
+                        writeln!(output, "no breakpoint hit after {executed} instructions")?;
+                        break;
+                    }
+                }
+                print_next(&emulator, output)?;
+            }
+            ("break" | "b", [address]) => match parse_number(address) {
+                Some(address) => {
+                    if !breakpoints.contains(&address) {
+                        breakpoints.push(address);
+                    }
+                    writeln!(output, "breakpoint set at {address:#05X}")?;
+                }
+                None => writeln!(output, "invalid address `{address}`")?,
+            },
+            ("regs" | "r", []) => print_registers(&emulator, output)?,
+            ("mem" | "m", [address, len]) => match (parse_number(address), parse_number(len)) {
+                (Some(address), Some(len)) => {
+                    write!(output, "{}", emulator.memory_hex_view(address, len))?
+                }
+                _ => writeln!(output, "usage: mem <addr> <len>")?,
+            },
+            ("disasm" | "d", []) => print_disassembly(&emulator, 10, output)?,
+            ("disasm" | "d", [count]) => match parse_number(count) {
+                Some(count) => print_disassembly(&emulator, count, output)?,
+                None => writeln!(output, "invalid count `{count}`")?,
+            },
+            ("reset", []) => {
+                emulator.reset();
+                print_next(&emulator, output)?;
+            }
+            ("quit" | "q", []) => return Ok(()),
+            ("help" | "h", []) => writeln!(output, "{HELP}")?,
+            _ => writeln!(output, "unknown command `{line}`, try `help`")?,
+        }
+
+        write!(output, "> ")?;
+        output.flush()?;
+    }
+    writeln!(output)
+}
+
+/// Accepts `0x2A0` style hex as well as decimal.
+fn parse_number(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn step(emulator: &mut Chip8, count: u64, output: &mut impl Write) -> io::Result<()> {
+    for _ in 0..count {
+        cli::step(emulator);
+        if let Some(fault) = emulator.fault() {
+            writeln!(output, "stopped: {fault}")?;
+            break;
+        }
+    }
+    print_next(emulator, output)
+}
+
+/// Shows the instruction PC points at, which runs on the next step.
+fn print_next(emulator: &Chip8, output: &mut impl Write) -> io::Result<()> {
+    print_disassembly(emulator, 1, output)
+}
+
+fn print_disassembly(emulator: &Chip8, count: usize, output: &mut impl Write) -> io::Result<()> {
+    let pc = emulator.pc();
+    let len = (count * 2).min(4096usize.saturating_sub(pc));
+    let code = emulator.read_memory(pc, len).unwrap_or(&[]);
+    write!(output, "{}", disassemble(code, pc))
+}
+
+fn print_registers(emulator: &Chip8, output: &mut impl Write) -> io::Result<()> {
+    for (row, registers) in emulator.registers().chunks(8).enumerate() {
+        let line: Vec<_> = (registers.iter().enumerate())
+            .map(|(column, value)| format!("V{:X} {value:02X}", row * 8 + column))
+            .collect();
+        writeln!(output, "{}", line.join("  "))?;
+    }
+    write!(
+        output,
+        "PC {:#05X}  I {:#05X}  DT {}  ST {}  stack [",
+        emulator.pc(),
+        emulator.index(),
+        emulator.delay_timer(),
+        emulator.sound_timer()
+    )?;
+    for (depth, address) in emulator.stack().iter().enumerate() {
+        let separator = if depth == 0 { "" } else { ", " };
+        write!(output, "{separator}{address:#05X}")?;
+    }
+    writeln!(output, "]")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session(rom: &[u8], commands: &str) -> String {
+        let mut output = Vec::new();
+        run(Chip8::from_bytes(rom), commands.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn piped_session() {
+        let rom = [
+            0x60, 0x05, // V0 = 5
+            0x70, 0x03, // V0 += 3
+            0x12, 0x04, // loop
+        ];
+        let commands = "step\nbreak 0x204\ncontinue\nregs\nmem 0x200 6\nquit\n";
+        let output = session(&rom, commands);
+        assert!(output.contains("0202  7003  ADD V0, 0x03"));
+        assert!(output.contains("breakpoint set at 0x204"));
+        assert!(output.contains("breakpoint at 0x204"));
+        assert!(output.contains("V0 08"));
+        assert!(output.contains("PC 0x204"));
+        assert!(output.contains("0200  60 05 70 03 12 04"));
+    }
+
+    #[test]
+    fn disasm_and_reset() {
+        let rom = [0x00, 0xE0, 0x12, 0x00];
+        let output = session(&rom, "step 3\ndisasm 2\nreset\nbogus\n");
+        assert!(output.contains("0202  1200  JP 0x200\n0204  0000  NOP"));
+        assert!(output.contains("0200  00E0  CLS"));
+        assert!(output.contains("unknown command `bogus`"));
+    }
+
+    #[test]
+    fn continue_until_fault() {
+        let rom = [0x00, 0xEE]; // return with an empty stack
+        let output = session(&rom, "continue\nstep\n");
+        let stops = output.matches("stopped: return with an empty stack");
+        assert_eq!(stops.count(), 2);
+        assert!(!output.contains("no breakpoint hit"));
+    }
+}
@@ -0,0 +1,72 @@
+use alloc::string::String;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// An access of `len` bytes starting at `address` doesn't fit in RAM.
+    MemoryOutOfBounds { address: usize, len: usize },
+    /// The data passed to `load_state` isn't a save state.
+    InvalidSaveState,
+    /// The save state's checksum doesn't match its contents, it got damaged.
+    CorruptSaveState,
+    /// The save state was made by a machine with `found` bytes of RAM.
+    SaveStateMismatch { expected: usize, found: usize },
+    /// A ROM of `size` bytes doesn't fit in the `max` bytes of RAM after its
+    /// load address, 0x200 unless the builder says otherwise.
+    RomTooLarge { size: usize, max: usize },
+    /// Entry number `entry` (from 0) of an input script isn't `key:down@cycle`
+    /// or `key:up@cycle`.
+    InvalidInputScript { entry: usize },
+    /// A saved display has to be 256 (low-res) or 1024 (high-res) bytes.
+    InvalidDisplay { len: usize },
+    /// Line `line` (from 1) of an assembly program can't be assembled.
+    InvalidAssembly { line: usize, reason: &'static str },
+    /// `Chip8Builder` was given settings the machine can't run with.
+    InvalidConfig { reason: &'static str },
+    /// XO-CHIP planes are picked with a mask of 0 to 3, not `mask`.
+    InvalidPlaneMask { mask: u8 },
+    /// 2NNN was run with all 16 stack entries in use.
+    StackOverflow,
+    /// 00EE was run with nothing on the stack to return to.
+    StackUnderflow,
+    /// Reading or writing a file failed, `message` says why.
+    Io { message: String },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::MemoryOutOfBounds { address, len } => write!(
+                f,
+                "memory access of {len} bytes at {address:#05X} is out of bounds"
+            ),
+            Chip8Error::InvalidSaveState => write!(f, "invalid save state"),
+            Chip8Error::CorruptSaveState => write!(f, "save state is corrupt"),
+            Chip8Error::SaveStateMismatch { expected, found } => write!(
+                f,
+                "save state is for {found} bytes of RAM, this machine has {expected}"
+            ),
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {size} bytes, at most {max} bytes fit in memory")
+            }
+            Chip8Error::InvalidInputScript { entry } => write!(
+                f,
+                "input script entry {entry} isn't of the form key:down@cycle or key:up@cycle"
+            ),
+            Chip8Error::InvalidDisplay { len } => {
+                write!(f, "a saved display is 256 or 1024 bytes, not {len}")
+            }
+            Chip8Error::InvalidAssembly { line, reason } => write!(f, "line {line}: {reason}"),
+            Chip8Error::InvalidConfig { reason } => write!(f, "invalid configuration: {reason}"),
+            Chip8Error::InvalidPlaneMask { mask } => {
+                write!(f, "plane mask {mask} isn't between 0 and 3")
+            }
+            Chip8Error::StackOverflow => write!(f, "call with a full stack"),
+            Chip8Error::StackUnderflow => write!(f, "return with an empty stack"),
+            Chip8Error::Io { message } => write!(f, "{message}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Chip8Error {}
@@ -0,0 +1,545 @@
+use crate::chip8::FONT;
+use crate::config::quirk_mut;
+use crate::{Chip8, Chip8Key, Quirks};
+#[cfg(feature = "audio")]
+use macroquad::audio::{load_sound_from_bytes, play_sound, stop_sound, PlaySoundParams, Sound};
+use macroquad::prelude::*;
+
+/// It will convert the input keys from the original keypad values
+/// to a modern keyboard. Since we are using macroquad we are going
+/// to return the coresponsing enum value from macroquad KeyCode.
+///
+/// First seen it: https://multigesture.net/articles/how-to-write-an-emulator-chip-8-interpreter/
+/// and thought it is a good idea.
+fn keypad_to_keyboard(key: Chip8Key) -> KeyCode {
+    match key.value() {
+        0x1 => KeyCode::Key1,
+        0x2 => KeyCode::Key2,
+        0x3 => KeyCode::Key3,
+        0xC => KeyCode::Key4,
+        0x4 => KeyCode::Q,
+        0x5 => KeyCode::W,
+        0x6 => KeyCode::E,
+        0xD => KeyCode::R,
+        0x7 => KeyCode::A,
+        0x8 => KeyCode::S,
+        0x9 => KeyCode::D,
+        0xE => KeyCode::F,
+        0xA => KeyCode::Z,
+        0x0 => KeyCode::X,
+        0xB => KeyCode::C,
+        _ => KeyCode::V,
+    }
+}
+
+/// The keypad key a keyboard key stands for, `None` if it isn't mapped.
+pub fn keyboard_to_keypad(code: KeyCode) -> Option<Chip8Key> {
+    Chip8Key::all().find(|key| keypad_to_keyboard(*key) == code)
+}
+
+/// Feeds the current keyboard state into the emulator keypad.
+pub fn update_keypad(emulator: &mut Chip8) {
+    for key in Chip8Key::all() {
+        emulator.set_key(key.into(), is_key_down(keypad_to_keyboard(key)));
+    }
+}
+
+/// Draws the display, stretched over the whole window unless a fixed scale
+/// is set.
+#[derive(Debug, Clone, Default)]
+pub struct Renderer {
+    scale: Option<u32>,
+    // foreground and background, white on black if not set
+    colors: Option<(Color, Color)>,
+    collision_highlight: Option<Color>,
+    flicker_reduction: bool,
+    // the emulator's screen at the last draw, to spot pixels that went dark
+    // since or to keep them lit for another frame
+    previous: Option<Box<[[u8; 128]; 64]>>,
+    // text drawn on top of the game, in display pixels
+    overlay: Option<Box<[[bool; 128]; 64]>>,
+    // the display on the GPU and the RGBA bytes it was made from, only
+    // uploaded again once the picture changes
+    texture: Option<(Texture2D, Vec<u8>)>,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws every CHIP-8 pixel as a `scale` x `scale` square and asks for a
+    /// window that fits the display exactly, 0 goes back to stretching.
+    pub fn set_render_scale(&mut self, scale: u32) {
+        self.scale = (scale > 0).then_some(scale);
+    }
+
+    /// Draws lit pixels in `foreground` and the rest in `background`.
+    pub fn set_colors(&mut self, foreground: Color, background: Color) {
+        self.colors = Some((foreground, background));
+    }
+
+    /// Shows pixels that went dark since the previous frame in `color` for a
+    /// frame, if the latest sprite draw erased any (see
+    /// `Chip8::last_collision_count`). `None`, the default, turns it off.
+    pub fn set_collision_highlight(&mut self, color: Option<Color>) {
+        self.collision_highlight = color;
+        self.previous = None;
+    }
+
+    /// Shows every pixel that was lit in this frame or the one before, so
+    /// sprites that are erased and drawn again a frame later don't flicker,
+    /// as in Pong or Brix. Off by default.
+    pub fn set_flicker_reduction(&mut self, on: bool) {
+        self.flicker_reduction = on;
+        self.previous = None;
+    }
+
+    /// Writes `text` over the game with its top left corner at display pixel
+    /// `(x, y)`, in the 4x5 font ROMs get for FX29. Only hex digits can be
+    /// shown, anything else leaves a gap. The text stays until
+    /// `clear_overlay` and never touches the emulator's screen.
+    pub fn draw_overlay_text(&mut self, x: usize, y: usize, text: &str) {
+        let blank = || Box::new([[false; 128]; 64]);
+        let overlay = self.overlay.get_or_insert_with(blank);
+        for (index, c) in text.chars().enumerate() {
+            let Some(digit) = c.to_digit(16) else {
+                continue;
+            };
+            let glyph = &FONT[digit as usize * 5..][..5];
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..4 {
+                    let (x, y) = (x + index * 5 + column, y + row);
+                    if bits & (0x80 >> column) != 0 && x < 128 && y < 64 {
+                        overlay[y][x] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn clear_overlay(&mut self) {
+        self.overlay = None;
+    }
+
+    /// The emulator's screen with the overlay text lit on top of it, and
+    /// the previous frame too with flicker reduction.
+    fn composite(&self, framebuffer: &[[u8; 128]; 64]) -> Box<[[u8; 128]; 64]> {
+        let mut screen = Box::new(*framebuffer);
+        if let (true, Some(previous)) = (self.flicker_reduction, &self.previous) {
+            let pixels = screen.iter_mut().flatten().zip(previous.iter().flatten());
+            pixels.for_each(|(pixel, previous)| *pixel |= previous);
+        }
+        if let Some(overlay) = &self.overlay {
+            for (row, overlay_row) in screen.iter_mut().zip(overlay.iter()) {
+                for (pixel, lit) in row.iter_mut().zip(overlay_row) {
+                    if *lit {
+                        *pixel |= 1;
+                    }
+                }
+            }
+        }
+        screen
+    }
+
+    /// Window size that fits a `width` x `height` display at the current
+    /// scale, `None` when stretching.
+    pub fn window_size(&self, width: usize, height: usize) -> Option<(u32, u32)> {
+        self.scale
+            .map(|scale| (scale * width as u32, scale * height as u32))
+    }
+
+    /// The display as RGBA bytes, row by row, in the colors `draw` uses and
+    /// with the overlay on top. Every pixel becomes a `scale` x `scale`
+    /// square, so the image is `width * scale` x `height * scale`.
+    pub fn rgba_frame(&self, emulator: &Chip8, scale: u32) -> Vec<u8> {
+        let (width, height) = (emulator.width(), emulator.height());
+        let scale = scale as usize;
+        let collided = emulator.last_collision_count() > 0;
+        let screen = self.composite(emulator.framebuffer());
+        let mut image = Vec::with_capacity(width * height * scale * scale * 4);
+        for (y, row) in screen[..height].iter().enumerate() {
+            let start = image.len();
+            for (x, pixel) in row[..width].iter().enumerate() {
+                let color = self.pixel_color(x, y, *pixel, collided);
+                let rgba: [u8; 4] = color.into();
+                for _ in 0..scale {
+                    image.extend_from_slice(&rgba);
+                }
+            }
+            for _ in 1..scale {
+                image.extend_from_within(start..start + width * scale * 4);
+            }
+        }
+        image
+    }
+
+    /// Color of the pixel at `(x, y)` given its current value.
+    fn pixel_color(&self, x: usize, y: usize, pixel: u8, collided: bool) -> Color {
+        let (foreground, background) = self.colors.unwrap_or((WHITE, BLACK));
+        if pixel != 0 {
+            return foreground;
+        }
+        match (self.collision_highlight, &self.previous) {
+            (Some(color), Some(previous)) if collided && previous[y][x] != 0 => color,
+            _ => background,
+        }
+    }
+
+    pub fn draw(&mut self, emulator: &Chip8) {
+        let (width, height) = (emulator.width(), emulator.height());
+        if let Some((window_width, window_height)) = self.window_size(width, height) {
+            let (window_width, window_height) = (window_width as f32, window_height as f32);
+            if (screen_width(), screen_height()) != (window_width, window_height) {
+                request_new_screen_size(window_width, window_height);
+            }
+        }
+
+        // one texture for the whole display, scaled up by the GPU
+        let image = self.rgba_frame(emulator, 1);
+        match &mut self.texture {
+            Some((_, uploaded)) if *uploaded == image => {}
+            // same size, the texture on the GPU can take the new picture
+            Some((texture, uploaded)) if uploaded.len() == image.len() => {
+                texture.update_from_bytes(width as u32, height as u32, &image);
+                *uploaded = image;
+            }
+            // first frame or a switch between low and high resolution
+            _ => {
+                let texture = Texture2D::from_rgba8(width as u16, height as u16, &image);
+                texture.set_filter(FilterMode::Nearest);
+                self.texture = Some((texture, image));
+            }
+        }
+        let size = match self.window_size(width, height) {
+            Some((width, height)) => vec2(width as f32, height as f32),
+            None => vec2(screen_width(), screen_height()),
+        };
+        let params = DrawTextureParams {
+            dest_size: Some(size),
+            ..Default::default()
+        };
+        if let Some((texture, _)) = &self.texture {
+            draw_texture_ex(texture, 0.0, 0.0, WHITE, params);
+        }
+        if self.collision_highlight.is_some() || self.flicker_reduction {
+            self.previous = Some(Box::new(*emulator.framebuffer()));
+        }
+    }
+}
+
+/// Settings overlay drawn over the game, opened and closed with Tab.
+///
+/// Shows the ROM, the clock and the quirks, which can be picked with the
+/// arrow keys and flipped with Enter or Space. The game is paused while the
+/// menu is open.
+#[derive(Debug, Clone, Default)]
+pub struct Menu {
+    open: bool,
+    // index into Quirks::describe
+    selected: usize,
+    // whether closing the menu should resume, the player may have paused
+    resume_on_close: bool,
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens or closes the menu, pausing and resuming the game with it.
+    pub fn toggle(&mut self, emulator: &mut Chip8) {
+        self.open = !self.open;
+        if self.open {
+            self.resume_on_close = !emulator.is_paused();
+            emulator.pause();
+        } else if self.resume_on_close {
+            emulator.resume();
+        }
+    }
+
+    /// Moves the selection `by` entries, wrapping around.
+    pub fn select(&mut self, by: isize) {
+        let len = Quirks::describe().len() as isize;
+        self.selected = (self.selected as isize + by).rem_euclid(len) as usize;
+    }
+
+    /// Flips the selected quirk.
+    pub fn activate(&mut self, emulator: &mut Chip8) {
+        let (name, _) = Quirks::describe()[self.selected];
+        let mut quirks = emulator.quirks();
+        if let Some(quirk) = quirk_mut(&mut quirks, name) {
+            *quirk = !*quirk;
+        }
+        emulator.set_quirks(quirks);
+    }
+
+    /// The text of the menu, `>` marks the selected quirk.
+    pub fn lines(&self, rom: &str, emulator: &Chip8) -> Vec<String> {
+        let hz = emulator.cycles_per_frame() * 60;
+        let mut lines = vec![format!("ROM: {rom}"), format!("Clock: {hz} Hz")];
+        lines.push(String::new());
+        let mut quirks = emulator.quirks();
+        for (index, (name, _)) in Quirks::describe().into_iter().enumerate() {
+            let marker = if index == self.selected { '>' } else { ' ' };
+            let on = quirk_mut(&mut quirks, name).is_some_and(|quirk| *quirk);
+            let state = if on { "on" } else { "off" };
+            lines.push(format!("{marker} {name}: {state}"));
+        }
+        lines
+    }
+
+    /// Reacts to this frame's key presses, call once per frame.
+    pub fn update(&mut self, emulator: &mut Chip8) {
+        if is_key_pressed(KeyCode::Tab) {
+            self.toggle(emulator);
+        }
+        if !self.open {
+            return;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.select(-1);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.select(1);
+        }
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Space) {
+            self.activate(emulator);
+        }
+    }
+
+    /// Draws the menu over whatever is on screen, if it's open.
+    pub fn draw(&self, rom: &str, emulator: &Chip8) {
+        if !self.open {
+            return;
+        }
+        let shade = Color::new(0.0, 0.0, 0.0, 0.8);
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), shade);
+        for (row, line) in self.lines(rom, emulator).iter().enumerate() {
+            draw_text(line, 20.0, 30.0 + 24.0 * row as f32, 24.0, WHITE);
+        }
+    }
+}
+
+/// Plays a square wave tone while the emulator beeps, see `Chip8::is_beeping`.
+#[cfg(feature = "audio")]
+#[derive(Debug, Default)]
+pub struct Beeper {
+    // None if the tone couldn't be loaded, the game then runs silently
+    sound: Option<Sound>,
+    playing: bool,
+}
+
+#[cfg(feature = "audio")]
+impl Beeper {
+    /// Loads the tone. Without an audio device macroquad plays nothing, the
+    /// emulator keeps running all the same.
+    pub async fn new() -> Self {
+        let sound = load_sound_from_bytes(&square_wave_wav()).await.ok();
+        Beeper {
+            sound,
+            playing: false,
+        }
+    }
+
+    /// Starts or stops the tone to match `emulator`, call once per frame. The
+    /// tone is only touched when that changes, and stops while paused.
+    pub fn update_audio(&mut self, emulator: &Chip8) {
+        let beeping = emulator.is_beeping() && !emulator.is_paused();
+        if beeping == self.playing {
+            return;
+        }
+        self.playing = beeping;
+        let Some(sound) = &self.sound else {
+            return;
+        };
+        if beeping {
+            let params = PlaySoundParams {
+                looped: true,
+                volume: 1.0,
+            };
+            play_sound(sound, params);
+        } else {
+            stop_sound(sound);
+        }
+    }
+}
+
+/// A 16 bit mono WAV file of a 441Hz square wave at 44100Hz, a whole number
+/// of 100 sample periods so it loops without a click.
+#[cfg(feature = "audio")]
+fn square_wave_wav() -> Vec<u8> {
+    const RATE: u32 = 44_100;
+    const PERIOD: usize = 100;
+    const SAMPLES: usize = PERIOD * 10;
+    const AMPLITUDE: i16 = i16::MAX / 4;
+
+    let data_len = (SAMPLES * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + SAMPLES * 2);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes()); // size of the rest of fmt
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&RATE.to_le_bytes());
+    wav.extend_from_slice(&(RATE * 2).to_le_bytes()); // bytes per second
+    wav.extend_from_slice(&2u16.to_le_bytes()); // bytes per sample
+    wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in 0..SAMPLES {
+        let high = sample % PERIOD < PERIOD / 2;
+        let value = if high { AMPLITUDE } else { -AMPLITUDE };
+        wav.extend_from_slice(&value.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keyboard_mapping() {
+        let key = |value| Chip8Key::new(value).unwrap();
+        assert_eq!(keypad_to_keyboard(key(0x4)), KeyCode::Q);
+        assert_eq!(keypad_to_keyboard(key(0x0)), KeyCode::X);
+        assert_eq!(keyboard_to_keypad(KeyCode::V), Some(key(0xF)));
+        assert_eq!(keyboard_to_keypad(KeyCode::Key4), Some(key(0xC)));
+        assert_eq!(keyboard_to_keypad(KeyCode::P), None);
+        assert_eq!(Chip8Key::new(0x10), None);
+    }
+
+    #[test]
+    fn fixed_scale_geometry() {
+        let mut renderer = Renderer::new();
+        assert_eq!(renderer.window_size(64, 32), None);
+
+        renderer.set_render_scale(10);
+        assert_eq!(renderer.window_size(64, 32), Some((640, 320)));
+        assert_eq!(renderer.window_size(128, 64), Some((1280, 640)));
+        let image = renderer.rgba_frame(&Chip8::blank(), 10);
+        assert_eq!(image.len(), 640 * 320 * 4);
+
+        renderer.set_render_scale(0);
+        assert_eq!(renderer.window_size(64, 32), None);
+    }
+
+    #[test]
+    fn rgba_frame_colors() {
+        let mut renderer = Renderer::new();
+        // I = font glyph 0, which starts with 0xF0, and draw its top row
+        let mut emulator = Chip8::from_opcodes(&[0xF029, 0xD001]);
+        emulator.step();
+        emulator.step();
+        let image = renderer.rgba_frame(&emulator, 1);
+        assert_eq!(image.len(), 64 * 32 * 4);
+        assert_eq!(image[..4], [255, 255, 255, 255]);
+        assert_eq!(image[4 * 4..5 * 4], [0, 0, 0, 255]);
+
+        let amber = Color::from_hex(0xFFB000);
+        renderer.set_colors(amber, Color::from_hex(0x102030));
+        let image = renderer.rgba_frame(&emulator, 2);
+        assert_eq!(image[..4], [0xFF, 0xB0, 0x00, 0xFF]);
+        // the second row of the square is a copy of the first
+        assert_eq!(image[128 * 4..128 * 4 + 4], [0xFF, 0xB0, 0x00, 0xFF]);
+        let below = 2 * 128 * 4; // row 1 of the display, which is dark
+        assert_eq!(image[below..below + 4], [0x10, 0x20, 0x30, 0xFF]);
+    }
+
+    #[test]
+    fn flicker_reduction_keeps_last_frame() {
+        let mut renderer = Renderer::new();
+        renderer.set_flicker_reduction(true);
+        let mut current = [[0; 128]; 64];
+        current[1][2] = 1;
+        let mut previous = [[0; 128]; 64];
+        previous[3][4] = 2;
+        renderer.previous = Some(Box::new(previous));
+
+        let screen = renderer.composite(&current);
+        assert_eq!((screen[1][2], screen[3][4]), (1, 2));
+        let lit = screen.iter().flatten().filter(|pixel| **pixel != 0);
+        assert_eq!(lit.count(), 2);
+
+        renderer.set_flicker_reduction(false);
+        renderer.previous = Some(Box::new(previous));
+        assert_eq!(renderer.composite(&current)[3][4], 0);
+    }
+
+    #[test]
+    fn overlay_leaves_the_screen_alone() {
+        let mut renderer = Renderer::new();
+        let emulator = Chip8::blank();
+        renderer.draw_overlay_text(2, 1, "1");
+        let screen = renderer.composite(emulator.framebuffer());
+
+        // the 1 glyph is 0x20, 0x60, 0x20, 0x20, 0x70
+        assert_eq!(screen[1][..8], [0, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(screen[5][..8], [0, 0, 0, 1, 1, 1, 0, 0]);
+        let screen = emulator.framebuffer();
+        assert!(screen.iter().flatten().all(|pixel| *pixel == 0));
+
+        renderer.clear_overlay();
+        let screen = renderer.composite(emulator.framebuffer());
+        assert_eq!(&*screen, emulator.framebuffer());
+    }
+
+    #[test]
+    fn collision_highlight() {
+        let mut renderer = Renderer::new();
+        let mut previous = [[0; 128]; 64];
+        previous[1][2] = 1;
+        renderer.previous = Some(Box::new(previous));
+        assert_eq!(renderer.pixel_color(2, 1, 0, true), BLACK);
+
+        renderer.set_collision_highlight(Some(RED));
+        renderer.previous = Some(Box::new(previous));
+        assert_eq!(renderer.pixel_color(2, 1, 0, true), RED);
+        assert_eq!(renderer.pixel_color(2, 1, 0, false), BLACK);
+        assert_eq!(renderer.pixel_color(2, 1, 1, true), WHITE);
+        assert_eq!(renderer.pixel_color(3, 1, 0, true), BLACK);
+    }
+
+    #[test]
+    fn menu_pauses_and_flips_quirks() {
+        let mut emulator = Chip8::blank();
+        let mut menu = Menu::new();
+        menu.toggle(&mut emulator);
+        assert!(menu.is_open() && emulator.is_paused());
+
+        menu.select(-1); // wraps to the last quirk
+        menu.activate(&mut emulator);
+        assert!(emulator.quirks().jump_with_vx);
+        let lines = menu.lines("pong.ch8", &emulator);
+        assert_eq!(lines[..2], ["ROM: pong.ch8", "Clock: 600 Hz"]);
+        assert_eq!(lines.last().unwrap(), "> jump_with_vx: on");
+
+        menu.toggle(&mut emulator);
+        assert!(!menu.is_open() && !emulator.is_paused());
+
+        // a game paused before opening the menu stays paused
+        emulator.pause();
+        menu.toggle(&mut emulator);
+        menu.toggle(&mut emulator);
+        assert!(emulator.is_paused());
+    }
+
+    #[test]
+    #[cfg(feature = "audio")]
+    fn square_wave() {
+        let wav = square_wave_wav();
+        assert_eq!(wav.len(), 44 + 2000);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 36 + 2000);
+        let sample = |index: usize| i16::from_le_bytes([wav[44 + index * 2], wav[45 + index * 2]]);
+        assert!(sample(0) > 0 && sample(49) > 0);
+        assert!(sample(50) < 0 && sample(99) < 0);
+        assert_eq!(sample(100), sample(0));
+    }
+}
@@ -0,0 +1,28 @@
+/// One of the 16 keypad keys, 0x0 to 0xF, the same for every frontend.
+///
+/// Each frontend maps its own key codes to and from these, the machine only
+/// ever sees them through `Chip8::set_key`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Chip8Key(u8);
+
+impl Chip8Key {
+    /// The key labelled `key`, `None` past 0xF.
+    pub fn new(key: u8) -> Option<Chip8Key> {
+        (key <= 0xF).then_some(Chip8Key(key))
+    }
+
+    /// Every key from 0x0 to 0xF.
+    pub fn all() -> impl Iterator<Item = Chip8Key> {
+        (0..16).map(Chip8Key)
+    }
+
+    pub fn value(self) -> u8 {
+        self.0
+    }
+}
+
+impl From<Chip8Key> for u8 {
+    fn from(key: Chip8Key) -> u8 {
+        key.0
+    }
+}
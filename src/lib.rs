@@ -0,0 +1,28 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod chip8;
+#[cfg(feature = "std")]
+pub mod config;
+mod error;
+#[cfg(feature = "std")]
+pub mod frontend;
+mod key;
+mod quirks;
+#[cfg(feature = "std")]
+pub mod runner;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod wasm;
+
+#[cfg(feature = "std")]
+pub use chip8::static_opcode_histogram;
+pub use chip8::{
+    assemble, describe_opcode, disassemble, disassemble_labeled, framebuffer_diff,
+    framebuffer_diff_overlay, lint_jumps, lockstep_diverge, required_features, supported_opcodes,
+    unsupported_spec_opcodes, Chip8, Chip8Builder, Chip8Event, FeatureSet, Instruction,
+    KeySampleGranularity, LintWarning, OpcodeHandler, QuirkReport, StateDiff, StepOutcome,
+};
+pub use error::Chip8Error;
+pub use key::Chip8Key;
+pub use quirks::Quirks;
@@ -1,9 +1,15 @@
-mod chip8;
+mod cli;
+mod debugger;
 
+use chip8::{config, frontend};
+use cli::Mode;
 use inquire::Select;
 use macroquad::prelude::*;
 use std::fs;
-use std::time::{Duration, SystemTime};
+use std::time::Instant;
+
+/// Size of a CHIP-8 pixel on screen, a low-res display makes a 640x320 window.
+const RENDER_SCALE: u32 = 10;
 
 fn get_roms() -> Vec<String> {
     let entries = fs::read_dir("roms").expect("No roms folder");
@@ -14,39 +20,107 @@ fn get_roms() -> Vec<String> {
             .file_name()
             .into_string()
             .expect("Can't convert file name to String");
-        roms.push(rom);
+        // settings and RPL flags kept next to the ROMs
+        if !rom.ends_with(".toml") && !rom.ends_with(".rpl") {
+            roms.push(rom);
+        }
     }
     roms
 }
 
-#[macroquad::main("BasicShapes")]
-async fn main() {
+fn main() {
+    match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Mode::Window) => {
+            let mut renderer = frontend::Renderer::new();
+            renderer.set_render_scale(RENDER_SCALE);
+            let (width, height) = renderer.window_size(64, 32).unwrap_or((800, 600));
+            let conf = Conf {
+                window_title: "BasicShapes".to_owned(),
+                window_width: width as i32,
+                window_height: height as i32,
+                ..Conf::default()
+            };
+            macroquad::Window::from_config(conf, run_window(renderer))
+        }
+        Ok(Mode::Run {
+            rom,
+            cycles,
+            dump_screen,
+        }) => match cli::run_headless(&rom, cycles, dump_screen) {
+            Ok(output) => print!("{output}"),
+            Err(err) => exit(err),
+        },
+        Ok(Mode::Debug { rom }) => match cli::load(&rom) {
+            Ok(emulator) => {
+                let stdin = std::io::stdin();
+                if let Err(err) = debugger::run(emulator, stdin.lock(), &mut std::io::stdout()) {
+                    exit(cli::CliError::Failed(err.to_string()));
+                }
+            }
+            Err(err) => exit(err),
+        },
+        Err(err) => exit(err),
+    }
+}
+
+fn exit(err: cli::CliError) -> ! {
+    eprintln!("chip8: {err}");
+    std::process::exit(err.exit_code())
+}
+
+async fn run_window(mut renderer: frontend::Renderer) {
     let options = get_roms();
     let ans = Select::new("What ROM do you want to run?", options).prompt();
     let selected_rom = ans.expect("No rom selected");
 
     let rom_path = format!("roms/{selected_rom}");
-    let mut emulator = chip8::Chip8::new(rom_path);
+    let settings = config::load_rom_settings(&rom_path).unwrap_or_default();
+    let rom = fs::read(&rom_path).expect("No source file found");
+    let mut emulator = match settings.builder().build(&rom) {
+        Ok(emulator) => emulator,
+        Err(err) => {
+            eprintln!("{rom_path}: {err}");
+            return;
+        }
+    };
+    renderer.set_colors(
+        settings.foreground.unwrap_or(WHITE),
+        settings.background.unwrap_or(BLACK),
+    );
+    if let Err(err) = emulator.set_rpl_file(format!("{rom_path}.rpl")) {
+        eprintln!("{err}");
+    }
 
-    // Chip8 timer should be updated at a rate of 60hz
-    // So will simulate that by decreasing the timer every 16.67ms
+    // the timers run at 60hz, advance runs however many frames fit in the
+    // time since the last call and carries the rest over so nothing drifts
+    let mut last_frame = Instant::now();
 
-    let mut curr_time = SystemTime::now();
-    let tick_timer = Duration::from_nanos(16_670_000);
+    let mut menu = frontend::Menu::new();
+    #[cfg(feature = "audio")]
+    let mut beeper = frontend::Beeper::new().await;
 
-    loop {
-        if let Ok(elapsed) = curr_time.elapsed() {
-            if elapsed > tick_timer {
-                emulator.tick();
-                curr_time = SystemTime::now();
-            }
-        } else {
-            eprintln!("Couldn't retrieve elapsed time from system timer");
+    // closing the window only asks to quit, so the flags can be saved first
+    prevent_quit();
+    while !is_quit_requested() {
+        menu.update(&mut emulator);
+        frontend::update_keypad(&mut emulator);
+
+        let now = Instant::now();
+        if let Err(err) = emulator.advance(now - last_frame) {
+            eprintln!("{err}");
+            emulator.pause();
         }
+        last_frame = now;
+        #[cfg(feature = "audio")]
+        beeper.update_audio(&emulator);
 
-        emulator.start_cycle();
-        emulator.update_screen();
+        renderer.draw(&emulator);
+        menu.draw(&selected_rom, &emulator);
 
         next_frame().await;
     }
+
+    if let Err(err) = emulator.shutdown() {
+        eprintln!("{err}");
+    }
 }
@@ -0,0 +1,107 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Behaviours that differ between CHIP-8 interpreters, ROMs written for one
+/// platform often misbehave if these don't match what they expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// In low-res mode, DXY0 draws a 16x16 sprite like it does in high-res.
+    /// Some SUPER-CHIP versions draw an 8x16 sprite instead.
+    pub lores_wide_sprites: bool,
+    /// 00FE and 00FF scale what's on screen to the new resolution instead of
+    /// clearing it, lighting 2x2 pixels per low-res pixel and keeping every
+    /// other pixel when going back.
+    pub keep_screen_on_resolution_switch: bool,
+    /// Sprites are cut off at the edges of the screen. Without it the parts
+    /// that don't fit wrap around to the other side.
+    pub clip_sprites: bool,
+    /// 8XY6 and 8XYE shift VY and store the result in VX, as on the COSMAC
+    /// VIP. Without it VX is shifted in place like on SUPER-CHIP.
+    pub shift_uses_vy: bool,
+    /// 8XY1, 8XY2 and 8XY3 set VF to 0 after the operation, as on the COSMAC
+    /// VIP. Without it VF is left alone unless it's the destination.
+    pub reset_vf_on_logic: bool,
+    /// FX55 and FX65 leave I pointing after the last register, as on the
+    /// COSMAC VIP. Without it I is left unchanged like on SUPER-CHIP.
+    pub load_store_increments_i: bool,
+    /// BNNN is read as SUPER-CHIP's BXNN, jumping to XNN + VX. Without it
+    /// the jump goes to NNN + V0 like on the COSMAC VIP.
+    pub jump_with_vx: bool,
+}
+
+/// The COSMAC VIP's behaviour, except for the SUPER-CHIP sprites.
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            lores_wide_sprites: true,
+            keep_screen_on_resolution_switch: false,
+            clip_sprites: true,
+            shift_uses_vy: true,
+            reset_vf_on_logic: true,
+            load_store_increments_i: true,
+            jump_with_vx: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// Name and explanation of every quirk, in field order, for settings UIs.
+    pub fn describe() -> Vec<(&'static str, &'static str)> {
+        vec![
+            (
+                "lores_wide_sprites",
+                "DXY0 draws a 16x16 sprite in low-res too, as on SUPER-CHIP 1.1; \
+                 older versions draw 8x16.",
+            ),
+            (
+                "keep_screen_on_resolution_switch",
+                "Switching resolution scales the screen instead of clearing it, \
+                 as on some SUPER-CHIP versions.",
+            ),
+            (
+                "clip_sprites",
+                "Sprites are cut off at the screen edges instead of wrapping \
+                 around, as on the COSMAC VIP and SUPER-CHIP.",
+            ),
+            (
+                "shift_uses_vy",
+                "8XY6 and 8XYE shift VY into VX, as on the COSMAC VIP; \
+                 SUPER-CHIP shifts VX in place.",
+            ),
+            (
+                "reset_vf_on_logic",
+                "8XY1, 8XY2 and 8XY3 clear VF, as on the COSMAC VIP; later \
+                 interpreters leave it alone.",
+            ),
+            (
+                "load_store_increments_i",
+                "FX55 and FX65 move I past the last register, as on the COSMAC \
+                 VIP; SUPER-CHIP leaves I alone.",
+            ),
+            (
+                "jump_with_vx",
+                "BXNN jumps to XNN + VX, as on SUPER-CHIP; the COSMAC VIP \
+                 jumps to NNN + V0.",
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn describe_every_quirk() {
+        // "Quirks { a: true, b: false }" lists the fields in order
+        let debug = format!("{:?}", Quirks::default());
+        let fields: Vec<_> = debug
+            .trim_start_matches("Quirks {")
+            .trim_end_matches('}')
+            .split(',')
+            .map(|field| field.split(':').next().unwrap().trim())
+            .collect();
+        let names: Vec<_> = Quirks::describe().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, fields);
+    }
+}
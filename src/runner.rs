@@ -0,0 +1,278 @@
+use crate::{Chip8, Chip8Error};
+use std::sync::mpsc::{self, Receiver, RecvError, SendError, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const FRAME_TIME: Duration = Duration::from_nanos(16_670_000);
+
+/// How the runner paces the frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RunMode {
+    /// 60 frames per second, for something a person is watching.
+    #[default]
+    RealTime,
+    /// Every frame right after the previous one, for tests and tools. Frames
+    /// queue up in `Runner::output` as fast as they are made, so keep reading.
+    Unthrottled,
+}
+
+/// Spaces frames out as `RunMode` says, call `wait` after every frame.
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    mode: RunMode,
+    next_frame: Instant,
+}
+
+impl Pacer {
+    pub fn new(mode: RunMode) -> Self {
+        Pacer {
+            mode,
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Counts from now again, e.g. after a pause, so there are no frames
+    /// to catch up on.
+    pub fn restart(&mut self) {
+        self.next_frame = Instant::now();
+    }
+
+    /// Sleeps until the next frame is due in `RunMode::RealTime`, returns
+    /// right away in `RunMode::Unthrottled`.
+    pub fn wait(&mut self) {
+        if self.mode == RunMode::Unthrottled {
+            return;
+        }
+        self.next_frame += FRAME_TIME;
+        if let Some(wait) = self.next_frame.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Command {
+    SetKey(u8, bool),
+    Pause,
+    Resume,
+    Reset,
+    LoadRom(Vec<u8>),
+    /// Answered with an `Output::State`
+    SaveState,
+}
+
+#[derive(Debug)]
+pub enum Output {
+    /// Packed framebuffer, see `Chip8::framebuffer_packed`
+    Frame(Vec<u8>),
+    State(Vec<u8>),
+    /// The machine hit an error and got paused
+    Error(Chip8Error),
+}
+
+/// Runs a `Chip8` on its own thread, at 60 frames per second unless spawned
+/// with `RunMode::Unthrottled`.
+///
+/// The thread stops when the `Runner` is dropped.
+pub struct Runner {
+    commands: Option<Sender<Command>>,
+    output: Receiver<Output>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Runner {
+    pub fn spawn(emulator: Chip8) -> Self {
+        Runner::spawn_with_mode(emulator, RunMode::RealTime)
+    }
+
+    pub fn spawn_with_mode(emulator: Chip8, mode: RunMode) -> Self {
+        let (commands, command_rx) = mpsc::channel();
+        let (output_tx, output) = mpsc::channel();
+        let handle = thread::spawn(move || run(emulator, mode, command_rx, output_tx));
+
+        Runner {
+            commands: Some(commands),
+            output,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn send(&self, command: Command) -> Result<(), SendError<Command>> {
+        match &self.commands {
+            Some(commands) => commands.send(command),
+            None => Err(SendError(command)),
+        }
+    }
+
+    /// Blocks until the emulator thread publishes something.
+    pub fn recv(&self) -> Result<Output, RecvError> {
+        self.output.recv()
+    }
+
+    pub fn output(&self) -> &Receiver<Output> {
+        &self.output
+    }
+
+    /// Stops the emulator thread and waits for it to finish.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        // closing the command channel is what tells the thread to exit
+        self.commands = None;
+        if let Some(handle) = self.handle.take() {
+            handle.join().expect("emulator thread panicked");
+        }
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Returns false once the runner is gone.
+fn handle_command(emulator: &mut Chip8, command: Command, output: &Sender<Output>) -> bool {
+    match command {
+        Command::SetKey(key, pressed) => emulator.set_key(key, pressed),
+        Command::Pause => emulator.pause(),
+        Command::Resume => emulator.resume(),
+        Command::Reset => emulator.reset(),
+        Command::LoadRom(rom) => emulator.load_rom(&rom),
+        Command::SaveState => return output.send(Output::State(emulator.save_state())).is_ok(),
+    }
+    true
+}
+
+fn run(mut emulator: Chip8, mode: RunMode, commands: Receiver<Command>, output: Sender<Output>) {
+    run_frames(&mut emulator, mode, &commands, &output);
+    if let Err(error) = emulator.shutdown() {
+        let _ = output.send(Output::Error(error));
+    }
+}
+
+/// Runs until the runner is gone.
+fn run_frames(
+    emulator: &mut Chip8,
+    mode: RunMode,
+    commands: &Receiver<Command>,
+    output: &Sender<Output>,
+) {
+    let mut pacer = Pacer::new(mode);
+    loop {
+        if emulator.is_paused() {
+            // nothing to do until we are told otherwise
+            let Ok(command) = commands.recv() else {
+                return;
+            };
+            if !handle_command(emulator, command, output) {
+                return;
+            }
+            pacer.restart();
+            continue;
+        }
+
+        loop {
+            match commands.try_recv() {
+                Ok(command) => {
+                    if !handle_command(emulator, command, output) {
+                        return;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+
+        if let Err(error) = emulator.run_frame() {
+            emulator.pause();
+            if output.send(Output::Error(error)).is_err() {
+                return;
+            }
+            continue;
+        }
+
+        if output
+            .send(Output::Frame(emulator.framebuffer_packed()))
+            .is_err()
+        {
+            return;
+        }
+
+        pacer.wait();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn next_frame(runner: &Runner) -> Vec<u8> {
+        loop {
+            if let Output::Frame(frame) = runner.recv().unwrap() {
+                return frame;
+            }
+        }
+    }
+
+    #[test]
+    fn runner_responds_to_commands() {
+        // draw the 0 glyph at (0, 0) then loop forever
+        let rom = [0xF0, 0x29, 0xD0, 0x15, 0x12, 0x04];
+        let runner = Runner::spawn(Chip8::from_bytes(&rom));
+
+        let frame = next_frame(&runner);
+        assert_eq!(frame.len(), 256);
+        assert_eq!(frame[0], 0xF0);
+
+        runner.send(Command::SetKey(0x5, true)).unwrap();
+        runner.send(Command::Pause).unwrap();
+        runner.send(Command::SaveState).unwrap();
+        let state = loop {
+            if let Output::State(state) = runner.recv().unwrap() {
+                break state;
+            }
+        };
+        let mut restored = Chip8::blank();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.framebuffer_packed()[0], 0xF0);
+
+        // a blank ROM never draws anything
+        runner.send(Command::LoadRom(vec![0x12, 0x00])).unwrap();
+        runner.send(Command::Resume).unwrap();
+        let frame = loop {
+            let frame = next_frame(&runner);
+            if frame[0] == 0 {
+                break frame;
+            }
+        };
+        assert!(frame.iter().all(|byte| *byte == 0));
+
+        runner.shutdown();
+    }
+
+    #[test]
+    fn unthrottled_frames() {
+        let emulator = Chip8::from_bytes(&[0x12, 0x00]);
+        let runner = Runner::spawn_with_mode(emulator, RunMode::Unthrottled);
+        let start = Instant::now();
+        for _ in 0..120 {
+            next_frame(&runner);
+        }
+        // two seconds worth of frames, paced it would take that long
+        assert!(start.elapsed() < FRAME_TIME * 60);
+        runner.shutdown();
+    }
+
+    #[test]
+    fn unthrottled_pacer_never_sleeps() {
+        let mut pacer = Pacer::new(RunMode::Unthrottled);
+        let start = Instant::now();
+        for _ in 0..1000 {
+            pacer.wait();
+        }
+        assert!(start.elapsed() < FRAME_TIME * 500);
+    }
+}
@@ -0,0 +1,26 @@
+use alloc::format;
+use js_sys::Uint8Array;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::Response;
+
+use crate::Chip8;
+
+/// Downloads the ROM at `url` with the browser's `fetch` and loads it into a
+/// new machine.
+///
+/// Fails with a JS string saying what went wrong, or with the error `fetch`
+/// itself threw, e.g. for a network error.
+pub async fn fetch_and_load(url: &str) -> Result<Chip8, JsValue> {
+    let window = web_sys::window().ok_or_else(|| JsValue::from_str("no window to fetch from"))?;
+    let response: Response = JsFuture::from(window.fetch_with_str(url))
+        .await?
+        .dyn_into()?;
+    if !response.ok() {
+        let message = format!("{url}: HTTP {}", response.status());
+        return Err(JsValue::from_str(&message));
+    }
+    let body = JsFuture::from(response.array_buffer()?).await?;
+    let rom = Uint8Array::new(&body).to_vec();
+    Chip8::try_from_bytes(&rom).map_err(|err| JsValue::from_str(&format!("{url}: {err}")))
+}